@@ -1,7 +1,11 @@
-use openidconnect::core::{CoreClient, CoreIdToken, CoreProviderMetadata, CoreResponseType};
+use openidconnect::core::{
+    CoreClient, CoreGenderClaim, CoreJsonWebKeyType, CoreJweContentEncryptionAlgorithm,
+    CoreJwsSigningAlgorithm, CoreProviderMetadata, CoreResponseType,
+};
 use openidconnect::reqwest::async_http_client;
-use openidconnect::{AuthenticationFlow, CsrfToken, Nonce};
+use openidconnect::{AdditionalClaims, AuthenticationFlow, CsrfToken, IdToken, Nonce};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use tide::http::{Method, Url};
 use tide::{Middleware, Next, Redirect, Request, Route, StatusCode};
@@ -25,6 +29,17 @@ pub struct Config {
     pub redirecter_url: RedirectUrl,
 
     pub login_landing_url: Url,
+
+    /// Ids of the users that are granted the admin role.
+    pub admins: HashSet<String>,
+
+    /// Group whose members are granted the admin role, looked up in
+    /// the groups the `groups_claim` claim of the ID token listed at
+    /// login.
+    pub admin_group: Option<String>,
+
+    /// Name of the ID token claim listing the groups of the user.
+    pub groups_claim: String,
 }
 
 #[derive(Debug)]
@@ -32,6 +47,9 @@ pub struct OpenIdConnectMiddleware {
     login_path: String,
     callback_path: String,
     login_landing_path: String,
+    admins: HashSet<String>,
+    admin_group: Option<String>,
+    groups_claim: String,
     client: CoreClient,
 }
 
@@ -39,6 +57,39 @@ pub struct OpenIdConnectMiddleware {
 enum MiddlewareSessionState {
     PreAuth(CsrfToken, Nonce),
     PostAuth(String),
+    /// Authenticated along with the groups listed in the ID token.
+    PostAuthWithGroups(String, Vec<String>),
+}
+
+/// The claims beyond the standard ones, where the groups of the user are.
+#[derive(Debug, Deserialize, Serialize)]
+struct ExtraClaims {
+    #[serde(flatten)]
+    claims: HashMap<String, serde_json::Value>,
+}
+
+impl AdditionalClaims for ExtraClaims {}
+
+type ExtraIdToken = IdToken<
+    ExtraClaims,
+    CoreGenderClaim,
+    CoreJweContentEncryptionAlgorithm,
+    CoreJwsSigningAlgorithm,
+    CoreJsonWebKeyType,
+>;
+
+impl ExtraClaims {
+    /// The groups listed in the claim, which may be a list or a single group.
+    fn groups(&self, claim: &str) -> Vec<String> {
+        match self.claims.get(claim) {
+            Some(serde_json::Value::Array(groups)) => groups
+                .iter()
+                .filter_map(|g| g.as_str().map(str::to_string))
+                .collect(),
+            Some(serde_json::Value::String(g)) => vec![g.clone()],
+            _ => Vec::new(),
+        }
+    }
 }
 
 impl OpenIdConnectMiddleware {
@@ -58,6 +109,9 @@ impl OpenIdConnectMiddleware {
             login_path: "/api/login".to_string(),
             callback_path: "/callback".to_string(),
             login_landing_path: config.login_landing_url.to_string(),
+            admins: config.admins.clone(),
+            admin_group: config.admin_group.clone(),
+            groups_claim: config.groups_claim.clone(),
             client,
         }
     }
@@ -110,7 +164,7 @@ impl OpenIdConnectMiddleware {
             // state.
             #[derive(Deserialize)]
             struct OpenIdCallback {
-                id_token: CoreIdToken,
+                id_token: ExtraIdToken,
                 state: String,
             }
 
@@ -145,12 +199,16 @@ impl OpenIdConnectMiddleware {
             })?;
             let id = id.replace('.', "_"); // I hope nobody gets the same id =)
 
+            // The groups are kept rather than the admin role, so that a
+            // change of the admin group applies to the existing sessions.
+            let groups = claims.additional_claims().groups(&self.groups_claim);
+
             // Add the user id to the session state in order to mark this
             // session as authenticated.
             req.session_mut()
                 .insert(
                     SESSION_KEY,
-                    MiddlewareSessionState::PostAuth(id.to_string()),
+                    MiddlewareSessionState::PostAuthWithGroups(id.to_string(), groups),
                 )
                 .map_err(|error| tide::http::Error::new(StatusCode::InternalServerError, error))?;
 
@@ -172,7 +230,7 @@ const SESSION_KEY: &str = "tide.oidc";
 
 pub(crate) enum OpenIdConnectRequestExtData {
     Unauthenticated {},
-    Authenticated { user_id: String },
+    Authenticated { user_id: String, is_admin: bool },
 }
 
 #[tide::utils::async_trait]
@@ -209,7 +267,16 @@ where
 
             req.set_ext(match session_data {
                 Some(MiddlewareSessionState::PostAuth(user_id)) => {
-                    OpenIdConnectRequestExtData::Authenticated { user_id }
+                    let is_admin = self.admins.contains(&user_id);
+                    OpenIdConnectRequestExtData::Authenticated { user_id, is_admin }
+                }
+                Some(MiddlewareSessionState::PostAuthWithGroups(user_id, groups)) => {
+                    let is_admin = self.admins.contains(&user_id)
+                        || self
+                            .admin_group
+                            .as_ref()
+                            .is_some_and(|group| groups.contains(group));
+                    OpenIdConnectRequestExtData::Authenticated { user_id, is_admin }
                 }
                 _ => OpenIdConnectRequestExtData::Unauthenticated {},
             });
@@ -229,6 +296,11 @@ pub trait OpenIdConnectRequestExt {
     /// Gets the Identity Provider-specific user id of the authenticated
     /// user, or `None` if the session has not been authenticated.
     fn user_id(&self) -> Option<String>;
+
+    /// Returns `true` if the request is authenticated as one of the
+    /// configured admins or a member of the admin group, `false`
+    /// otherwise.
+    fn is_admin(&self) -> bool;
 }
 
 impl<State> OpenIdConnectRequestExt for Request<State>
@@ -248,6 +320,13 @@ where
             _ => None,
         }
     }
+
+    fn is_admin(&self) -> bool {
+        matches!(
+            self.auth_state(),
+            OpenIdConnectRequestExtData::Authenticated { is_admin: true, .. }
+        )
+    }
 }
 
 pub(crate) trait OpenIdConnectRequestExtInternal {
@@ -322,12 +401,21 @@ pub trait OpenIdConnectRouteExt {
     /// route, redirecting the browser to the login page if the request
     /// is not authenticated.
     fn authenticated(&mut self) -> &mut Self;
+
+    /// Requires the request to be authenticated as an admin on the
+    /// subsequent portions of this route, returning `401` for the
+    /// unauthenticated requests and `403` for the other users.
+    fn admin(&mut self) -> &mut Self;
 }
 
 impl<'a, State: Debug + Clone + Send + Sync + 'static> OpenIdConnectRouteExt for Route<'a, State> {
     fn authenticated(&mut self) -> &mut Self {
         self.with(MustAuthenticateMiddleware {})
     }
+
+    fn admin(&mut self) -> &mut Self {
+        self.with(MustBeAdminMiddleware {})
+    }
 }
 
 #[derive(Debug)]
@@ -360,3 +448,38 @@ where
         }
     }
 }
+
+#[derive(Debug)]
+struct MustBeAdminMiddleware;
+
+#[tide::utils::async_trait]
+impl<State> Middleware<State> for MustBeAdminMiddleware
+where
+    State: Debug + Clone + Send + Sync + 'static,
+{
+    #[instrument(name = "MustBeAdminMiddleware::handle", skip_all)]
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        match req.auth_state() {
+            OpenIdConnectRequestExtData::Authenticated { is_admin: true, .. } => {
+                tide::log::debug!(
+                    "Admin request; forwarding request to next item in middleware chain."
+                );
+                Ok(next.run(req).await)
+            }
+            OpenIdConnectRequestExtData::Authenticated { .. } => {
+                tide::log::debug!("Non-admin request; returning 403");
+                Err(tide::http::Error::from_str(
+                    StatusCode::Forbidden,
+                    "Admin role required to access this endpoint",
+                ))
+            }
+            OpenIdConnectRequestExtData::Unauthenticated {} => {
+                tide::log::debug!("Unauthenticated request; returning 401");
+                Err(tide::http::Error::from_str(
+                    StatusCode::Unauthorized,
+                    "Auth required to access this endpoint",
+                ))
+            }
+        }
+    }
+}
@@ -1,4 +1,10 @@
 pub use sea_orm;
 
+pub mod house_strategy;
+pub mod match_result;
+pub mod player_result;
+pub mod round;
 pub mod round_result;
+pub mod scoreboard_entry;
 pub mod submission;
+pub mod user;
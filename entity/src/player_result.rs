@@ -0,0 +1,42 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Result of one of the players in a match, along with who they played against
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "player_results")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[serde(skip_deserializing)]
+    pub id: i32,
+    pub match_id: i32,
+    pub round_id: i32,
+    pub user_id: String,
+    /// `0` for the house strategies
+    pub submission_id: i32,
+    pub opponent_user_id: String,
+    pub opponent_submission_id: i32,
+    /// Whether the player moved first in the match
+    pub first: bool,
+    /// `None` if the player failed
+    pub score: Option<f64>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub error: Option<String>,
+    /// JSON list of the moves
+    #[sea_orm(column_type = "Text")]
+    pub moves: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// The score of the player or the error they failed with
+    pub fn outcome(&self) -> Result<f64, String> {
+        match (&self.error, self.score) {
+            (Some(e), _) => Err(e.clone()),
+            (None, score) => Ok(score.unwrap_or_default()),
+        }
+    }
+}
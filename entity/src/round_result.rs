@@ -1,6 +1,8 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
+/// The rounds stored as JSON blobs, copied to `rounds`, `matches` and `player_results`
+/// by m20220328_000008_normalize_round_results and kept for rolling it back, only used by the migrations
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Deserialize, Serialize)]
 #[sea_orm(table_name = "round_results")]
 pub struct Model {
@@ -12,6 +12,27 @@ pub struct Model {
     pub code: String,
     pub datetime: DateTimeUtc,
     pub valid: bool,
+    /// JSON list of the source files for the multi-file submissions, `code` has all of them joined
+    #[sea_orm(column_type = "Text", nullable)]
+    pub files: Option<String>,
+    /// Major version of the JDK the submission is compiled and run with
+    pub jdk_version: i32,
+    /// JSON-serialized validation report, absent for the submissions made before it was stored
+    #[sea_orm(column_type = "Text", nullable)]
+    pub report: Option<String>,
+    /// The multiplier of the grade applied for being late, absent for the submissions made in time
+    pub late_penalty: Option<f64>,
+    /// The submission can play in the rounds, the late ones may be accepted only for grading
+    pub ranked: bool,
+    /// Invalidated by an admin, it counts as not valid regardless of the validation
+    pub invalidated: bool,
+}
+
+impl Model {
+    /// Passed the validation and was not invalidated by an admin
+    pub fn is_valid(&self) -> bool {
+        self.valid && !self.invalidated
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -0,0 +1,25 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Per-user tournament settings, users without a row use the defaults
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "users")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub user_id: String,
+    /// The submission playing in the rounds, the latest valid one if not set
+    pub active_submission: Option<i32>,
+    /// The user does not take part in the rounds
+    pub withdrawn: bool,
+    /// Others can play against the active submission of the user in the sparring playground
+    pub sparring_partner: bool,
+    /// Overrides the submission deadline of the contest for the user, e.g. for an approved extension
+    pub deadline: Option<DateTimeUtc>,
+    /// Banned by an admin: cannot submit and does not play in the rounds
+    pub banned: bool,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
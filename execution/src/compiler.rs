@@ -1,48 +1,91 @@
+use crate::diagnostics::{parse_javac_output, Diagnostic};
 use crate::docker_util::run_container;
 use crate::error::Error::CompilationError;
-use futures_util::stream::StreamExt;
-use shiplift::{ContainerOptions, Docker, PullOptions};
+use crate::jdk::{JdkImages, JdkVersion};
+use shiplift::{ContainerOptions, Docker};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Debug, Formatter};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tempfile::{tempdir, TempDir};
-use tracing::{debug, info, instrument, trace};
+use tracing::{instrument, trace};
 
 pub struct JavaCompiler {
     docker: Docker,
-    image_name: String,
+    images: JdkImages,
 }
 
 impl Debug for JavaCompiler {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("JavaCompiler")
-            .field("image_name", &self.image_name)
+            .field("images", &self.images)
             .finish()
     }
 }
 
-const IMAGE_NAME: &str = "openjdk:8-alpine";
 const TIMEOUT: Duration = Duration::from_secs(60);
+/// Where the program directory is mounted inside the compiler and runner containers
+pub const APP_DIR: &str = "/app";
 
 impl JavaCompiler {
-    pub async fn new(docker: Docker) -> Result<JavaCompiler, anyhow::Error> {
-        info!("Gonna pull image {IMAGE_NAME}");
-        {
-            let mut stream = docker
-                .images()
-                .pull(&PullOptions::builder().image(IMAGE_NAME).build());
-
-            while let Some(pull_result) = stream.next().await {
-                debug!("Pull message: {}", pull_result?);
-            }
+    pub async fn new(docker: Docker, images: JdkImages) -> Result<JavaCompiler, anyhow::Error> {
+        images.pull(&docker).await?;
+
+        Ok(Self { docker, images })
+    }
+
+    /// Runs javac of the program JDK on the classes in the program directory, targeting the `release` JDK.
+    ///
+    /// Returns the exit code and the javac output
+    async fn run_javac(
+        &self,
+        dir: &Path,
+        jdk: JdkVersion,
+        release: JdkVersion,
+        classes: &[&JavaClass],
+        implicit: bool,
+    ) -> Result<(u64, String), anyhow::Error> {
+        let image_name = self.images.image(jdk)?;
+
+        let mounts = [format!("{}:{APP_DIR}", dir.to_str().unwrap())];
+        // -Xlint to report the warnings even when the compilation succeeds
+        let mut cmd: Vec<String> = ["javac", "-Xlint", "-sourcepath", APP_DIR]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        if release != jdk {
+            // the language level and the API of the older JDK, but the class files the runner can load
+            cmd.extend(["--release".to_string(), release.to_string()]);
+        }
+        if !implicit {
+            // the shared classes are compiled for the JDK of the program
+            cmd.push("-implicit:none".to_string());
         }
 
-        Ok(Self {
-            docker,
-            image_name: IMAGE_NAME.to_string(),
-        })
+        cmd.extend(classes.iter().map(|p| {
+            Path::new(APP_DIR)
+                .join(p.relative_path())
+                .to_str()
+                .unwrap()
+                .to_string()
+        }));
+
+        trace!("Creating compiler container for JDK {release}...");
+        let container = ContainerOptions::builder(image_name)
+            .volumes(mounts.iter().map(|s| s.as_str()).collect())
+            .cmd(cmd.iter().map(|s| s.as_str()).collect())
+            .network_mode("none")
+            .memory(67108864) // 64 MiB
+            // .attach_stderr(true)
+            // .attach_stdout(true)
+            .build();
+
+        let (exit, _, err) = run_container(&self.docker, &container, TIMEOUT).await?;
+
+        Ok((exit.status_code, err))
     }
 
+    /// Compiles each class for the JDK it was added with, using the JDK of the program that also runs it
     #[instrument(skip(program))]
     pub async fn compile(
         &self,
@@ -50,91 +93,185 @@ impl JavaCompiler {
     ) -> Result<CompiledJavaProgram, anyhow::Error> {
         let dir = tempdir()?;
 
-        trace!("Compiling java program in {dir:?}");
+        trace!("Compiling java program in {dir:?} with JDK {}", program.jdk);
 
         let mut java_paths = Vec::new();
 
-        for JavaClass {
-            full_name,
-            source_code,
-        } in program.0.iter()
-        {
-            let path = full_name.replace('.', "/") + ".java";
-            let path = dir.path().join(path);
+        for class in program.classes.iter() {
+            let path = dir.path().join(class.relative_path());
 
             std::fs::create_dir_all(path.parent().unwrap())?;
-            std::fs::write(path.clone(), source_code)?;
+            std::fs::write(path.clone(), &class.source_code)?;
 
             java_paths.push(path);
         }
 
-        let mounts = vec![format!("{}:/app", dir.path().to_str().unwrap())];
-        let mut cmd: Vec<String> = ["javac", "-sourcepath", "/app"]
-            .iter()
-            .map(|s| s.to_string())
-            .collect();
-
-        cmd.extend(
-            program
-                .0
-                .iter()
-                .map(|p| format!("/app/{}.java", p.full_name.replace('.', "/"))),
-        );
-
-        trace!("Creating compiler container...");
-        let container = ContainerOptions::builder(&self.image_name)
-            .volumes(mounts.iter().map(|s| s.as_str()).collect())
-            .cmd(cmd.iter().map(|s| s.as_str()).collect())
-            .network_mode("none")
-            .memory(67108864) // 64 MiB
-            // .attach_stderr(true)
-            // .attach_stdout(true)
-            .build();
+        let mut groups: BTreeMap<JdkVersion, Vec<&JavaClass>> = BTreeMap::new();
+        for class in program.classes.iter() {
+            groups.entry(class.jdk).or_default().push(class);
+        }
+        // the JDK of the program goes last, so that the shared classes are compiled for it
+        let program_group = groups.remove(&program.jdk);
 
-        let (exit, _, err) = run_container(&self.docker, &container, TIMEOUT).await?;
+        let mut output = String::new();
+        let groups = groups
+            .into_iter()
+            .map(|(jdk, classes)| (jdk, classes, false))
+            .chain(program_group.map(|classes| (program.jdk, classes, true)));
+        for (jdk, classes, implicit) in groups {
+            let (status_code, err) = self
+                .run_javac(dir.path(), program.jdk, jdk, &classes, implicit)
+                .await?;
+            output.push_str(&err);
 
-        if exit.status_code != 0 {
-            return Err(CompilationError(err).into());
+            if status_code != 0 {
+                let diagnostics = program.resolve_diagnostics(parse_javac_output(&output));
+                return Err(CompilationError(output, diagnostics).into());
+            }
         }
 
+        let diagnostics = program.resolve_diagnostics(parse_javac_output(&output));
+
         trace!("javac succeeded, removing source code");
         for class in java_paths {
             std::fs::remove_file(class)?
         }
 
-        Ok(CompiledJavaProgram { directory: dir })
+        Ok(CompiledJavaProgram {
+            directory: dir,
+            jdk: program.jdk,
+            diagnostics,
+        })
     }
 }
 
+/// Where the source code of a class came from, for reporting the diagnostics
+#[derive(Debug, Clone)]
+pub struct SourceOrigin {
+    pub owner: String,
+    pub file_name: String,
+}
+
 #[derive(Debug)]
 pub struct JavaClass {
+    /// Class root directory relative to the program directory, empty for the shared classes
+    pub root: String,
+    /// The JDK the class is compiled for
+    pub jdk: JdkVersion,
     pub full_name: String,
     pub source_code: String,
+    pub origin: Option<SourceOrigin>,
+}
+
+impl JavaClass {
+    fn relative_path(&self) -> PathBuf {
+        Path::new(&self.root).join(self.full_name.replace('.', "/") + ".java")
+    }
 }
 
 #[derive(Debug)]
-pub struct JavaProgram(Vec<JavaClass>);
+pub struct JavaProgram {
+    jdk: JdkVersion,
+    classes: Vec<JavaClass>,
+}
 
 impl JavaProgram {
-    pub fn new() -> Self {
-        JavaProgram(Vec::new())
+    pub fn new(jdk: JdkVersion) -> Self {
+        JavaProgram {
+            jdk,
+            classes: Vec::new(),
+        }
     }
 
     pub fn push_class(&mut self, full_name: String, source_code: String) {
-        self.0.push(JavaClass {
-            full_name: full_name.to_string(),
+        self.classes.push(JavaClass {
+            root: String::new(),
+            jdk: self.jdk,
+            full_name,
+            source_code,
+            origin: None,
+        })
+    }
+
+    /// Adds a class that will be compiled into a separate class root for the given JDK,
+    /// so it can be loaded in isolation from the rest of the program.
+    ///
+    /// The JDK of the program should not be older, as it compiles and runs the class
+    pub fn push_class_at(
+        &mut self,
+        root: String,
+        jdk: JdkVersion,
+        full_name: String,
+        source_code: String,
+        origin: SourceOrigin,
+    ) {
+        self.classes.push(JavaClass {
+            root,
+            jdk,
+            full_name,
             source_code,
+            origin: Some(origin),
+        })
+    }
+
+    /// Removes the classes of the owners from the program
+    pub fn remove_owners(&mut self, owners: &BTreeSet<String>) {
+        self.classes.retain(|c| match &c.origin {
+            Some(origin) => !owners.contains(&origin.owner),
+            None => true,
         })
     }
+
+    /// Rewrites the paths inside the compiler container to the names of the files they came from
+    fn resolve_diagnostics(&self, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        let app_dir = Path::new(APP_DIR);
+
+        diagnostics
+            .into_iter()
+            .map(|mut d| {
+                let class = d.file.as_ref().and_then(|f| {
+                    self.classes
+                        .iter()
+                        .find(|c| app_dir.join(c.relative_path()) == Path::new(f))
+                });
+                match class {
+                    Some(JavaClass {
+                        origin: Some(origin),
+                        ..
+                    }) => {
+                        d.owner = Some(origin.owner.clone());
+                        d.file = Some(origin.file_name.clone());
+                    }
+                    Some(class) => {
+                        d.file = Some(class.relative_path().to_string_lossy().into_owned());
+                    }
+                    None => {}
+                }
+                d
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug)]
 pub struct CompiledJavaProgram {
     directory: TempDir,
+    jdk: JdkVersion,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl CompiledJavaProgram {
     pub fn path(&self) -> &Path {
         self.directory.path()
     }
+
+    /// The JDK the program was compiled with and should be run with
+    pub fn jdk(&self) -> JdkVersion {
+        self.jdk
+    }
+
+    /// The warnings reported by javac
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
 }
@@ -0,0 +1,297 @@
+//! Parsing of the javac output into structured diagnostics.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The player the file belongs to, `None` for the fixture code and the diagnostics without a location
+    pub owner: Option<String>,
+    /// Name of the file as it was submitted
+    pub file: Option<String>,
+    /// 1-based
+    pub line: Option<u32>,
+    /// 1-based
+    pub column: Option<u32>,
+    pub message: String,
+    /// The source line the diagnostic points to
+    pub snippet: Option<String>,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{file}:")?;
+            if let Some(line) = self.line {
+                write!(f, "{line}:")?;
+                if let Some(column) = self.column {
+                    write!(f, "{column}:")?;
+                }
+            }
+            write!(f, " ")?;
+        }
+        write!(f, "{}: {}", self.severity, self.message)?;
+
+        if let Some(snippet) = &self.snippet {
+            write!(f, "\n{snippet}")?;
+            if let Some(column) = self.column {
+                let indent: String = snippet
+                    .chars()
+                    .take(column as usize - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                write!(f, "\n{indent}^")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_severity(s: &str) -> Option<(Severity, &str)> {
+    [
+        ("error: ", Severity::Error),
+        ("warning: ", Severity::Warning),
+        ("note: ", Severity::Note),
+        ("Note: ", Severity::Note),
+    ]
+    .into_iter()
+    .find_map(|(prefix, severity)| s.strip_prefix(prefix).map(|m| (severity, m)))
+}
+
+/// Parses `<path>.java:<line>: <severity>: <message>` or `<severity>: <message>`
+fn parse_header(line: &str) -> Option<Diagnostic> {
+    let (file, line_number, rest) = match line.find(".java:") {
+        Some(i) => {
+            let (file, rest) = line.split_at(i + ".java".len());
+            let rest = &rest[1..];
+            let (number, rest) = rest.split_once(": ")?;
+            (Some(file.to_string()), Some(number.parse().ok()?), rest)
+        }
+        None => (None, None, line),
+    };
+
+    let (severity, message) = parse_severity(rest)?;
+
+    Some(Diagnostic {
+        severity,
+        owner: None,
+        file,
+        line: line_number,
+        column: None,
+        message: message.to_string(),
+        snippet: None,
+    })
+}
+
+/// The `1 error`/`2 warnings` lines at the end of the output
+fn is_summary(line: &str) -> bool {
+    matches!(
+        line.split_once(' '),
+        Some((count, "error" | "errors" | "warning" | "warnings"))
+            if count.chars().all(|c| c.is_ascii_digit())
+    )
+}
+
+fn is_caret(line: &str) -> bool {
+    line.trim() == "^"
+}
+
+fn finish(mut diagnostic: Diagnostic, body: &[&str]) -> Diagnostic {
+    let mut details = Vec::new();
+
+    match body.iter().position(|l| is_caret(l)) {
+        Some(caret) if caret > 0 => {
+            diagnostic.snippet = Some(body[caret - 1].to_string());
+            diagnostic.column = Some(
+                body[caret][..body[caret].find('^').unwrap()]
+                    .chars()
+                    .count() as u32
+                    + 1,
+            );
+            details.extend(&body[..caret - 1]);
+            details.extend(&body[caret + 1..]);
+        }
+        _ => details.extend(body),
+    }
+
+    for l in details {
+        diagnostic.message.push('\n');
+        diagnostic.message.push_str(l);
+    }
+
+    diagnostic
+}
+
+/// Parses the output javac produces with the default diagnostic format.
+///
+/// The `file` of the diagnostics is the path javac was given
+pub fn parse_javac_output(output: &str) -> Vec<Diagnostic> {
+    let mut res = Vec::new();
+    let mut current: Option<(Diagnostic, Vec<&str>)> = None;
+
+    for line in output.lines() {
+        let header = parse_header(line);
+        if header.is_some() || is_summary(line) {
+            if let Some((diagnostic, body)) = current.take() {
+                res.push(finish(diagnostic, &body));
+            }
+            current = header.map(|h| (h, Vec::new()));
+        } else if let Some((_, body)) = &mut current {
+            body.push(line);
+        }
+    }
+    if let Some((diagnostic, body)) = current {
+        res.push(finish(diagnostic, &body));
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(
+        severity: Severity,
+        file: Option<&str>,
+        line: Option<u32>,
+        column: Option<u32>,
+        message: &str,
+        snippet: Option<&str>,
+    ) -> Diagnostic {
+        Diagnostic {
+            severity,
+            owner: None,
+            file: file.map(str::to_string),
+            line,
+            column,
+            message: message.to_string(),
+            snippet: snippet.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn error_with_caret() {
+        let output = "\
+/app/player_0/Strat.java:13: error: ';' expected
+        return x
+                ^
+1 error
+";
+        assert_eq!(
+            parse_javac_output(output),
+            vec![diagnostic(
+                Severity::Error,
+                Some("/app/player_0/Strat.java"),
+                Some(13),
+                Some(17),
+                "';' expected",
+                Some("        return x"),
+            )]
+        );
+    }
+
+    #[test]
+    fn errors_and_lint_warnings() {
+        let output = "\
+/app/player_0/Strat.java:11: warning: [unchecked] unchecked call to add(E) as a member of the raw type List
+        raw.add(1);
+               ^
+  where E is a type-variable:
+    E extends Object declared in interface List
+/app/player_0/Strat.java:13: error: cannot find symbol
+        return y;
+               ^
+  symbol:   variable y
+  location: class Strat
+1 error
+1 warning
+";
+        assert_eq!(
+            parse_javac_output(output),
+            vec![
+                diagnostic(
+                    Severity::Warning,
+                    Some("/app/player_0/Strat.java"),
+                    Some(11),
+                    Some(16),
+                    "[unchecked] unchecked call to add(E) as a member of the raw type List\n  where E is a type-variable:\n    E extends Object declared in interface List",
+                    Some("        raw.add(1);"),
+                ),
+                diagnostic(
+                    Severity::Error,
+                    Some("/app/player_0/Strat.java"),
+                    Some(13),
+                    Some(16),
+                    "cannot find symbol\n  symbol:   variable y\n  location: class Strat",
+                    Some("        return y;"),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn notes_without_location() {
+        let output = "\
+Note: /app/player_0/Ok.java uses or overrides a deprecated API.
+Note: Recompile with -Xlint:deprecation for details.
+";
+        assert_eq!(
+            parse_javac_output(output),
+            vec![
+                diagnostic(
+                    Severity::Note,
+                    None,
+                    None,
+                    None,
+                    "/app/player_0/Ok.java uses or overrides a deprecated API.",
+                    None,
+                ),
+                diagnostic(
+                    Severity::Note,
+                    None,
+                    None,
+                    None,
+                    "Recompile with -Xlint:deprecation for details.",
+                    None,
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn display_points_at_the_column() {
+        let d = diagnostic(
+            Severity::Error,
+            Some("Strat.java"),
+            Some(13),
+            Some(17),
+            "';' expected",
+            Some("        return x"),
+        );
+        assert_eq!(
+            d.to_string(),
+            "Strat.java:13:17: error: ';' expected\n        return x\n                ^"
+        );
+    }
+}
@@ -68,5 +68,5 @@ pub async fn run_container(
         .remove(RmContainerOptions::builder().force(true).build())
         .await?;
 
-    Ok(wait_res?)
+    wait_res
 }
@@ -1,10 +1,15 @@
+use crate::diagnostics::Diagnostic;
+use crate::jdk::JdkVersion;
 use std::{error, fmt};
 
 #[derive(Debug)]
 pub enum Error {
-    CompilationError(String),
+    /// The raw javac output and the diagnostics parsed from it
+    CompilationError(String, Vec<Diagnostic>),
+    InvalidSource(String),
     ExecutionTimeout,
     FixtureFailure(u64, String, String, Option<anyhow::Error>),
+    UnsupportedJdk(JdkVersion),
 }
 
 impl fmt::Display for Error {
@@ -0,0 +1,309 @@
+//! Lexer-based inspection and patching of the player source code.
+
+use crate::lexer::{tokenize, Token, TokenKind};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeDeclaration {
+    pub name: String,
+    pub is_public: bool,
+    /// Whether the type lists `Player` (maybe qualified) in its `implements` clause
+    pub implements_player: bool,
+}
+
+fn code_tokens(code: &str) -> Vec<Token<'_>> {
+    tokenize(code)
+        .into_iter()
+        .filter(|t| t.kind != TokenKind::Comment)
+        .collect()
+}
+
+fn is_symbol(token: Option<&Token>, symbol: &str) -> bool {
+    matches!(token, Some(t) if t.kind == TokenKind::Symbol && t.text == symbol)
+}
+
+fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+    matches!(token, Some(t) if t.kind == TokenKind::Keyword && t.text == keyword)
+}
+
+fn is_identifier(token: Option<&Token>) -> bool {
+    matches!(token, Some(t) if t.kind == TokenKind::Identifier)
+}
+
+/// Returns the index of the token following the annotation starting at `i`
+fn skip_annotation(tokens: &[Token], mut i: usize) -> usize {
+    // the '@'
+    i += 1;
+
+    // the (maybe qualified) name
+    if is_identifier(tokens.get(i)) {
+        i += 1;
+        while is_symbol(tokens.get(i), ".") && is_identifier(tokens.get(i + 1)) {
+            i += 2;
+        }
+    }
+
+    // the arguments
+    if is_symbol(tokens.get(i), "(") {
+        let mut depth = 0;
+        while let Some(t) = tokens.get(i) {
+            i += 1;
+            if is_symbol(Some(t), "(") {
+                depth += 1;
+            } else if is_symbol(Some(t), ")") {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    i
+}
+
+/// Sets the package of the compilation unit, replacing the existing package declaration or inserting a new one.
+///
+/// The line numbers of the code are preserved.
+pub fn patch_package(code: &str, package_name: &str) -> String {
+    // javac does not accept the byte order mark
+    let code = code.strip_prefix('\u{feff}').unwrap_or(code);
+    let tokens = code_tokens(code);
+    let declaration = format!("package {package_name};");
+
+    let mut i = 0;
+    while is_symbol(tokens.get(i), "@") && !is_keyword(tokens.get(i + 1), "interface") {
+        i = skip_annotation(&tokens, i);
+    }
+
+    let start = tokens.first().map(|t| t.span.start).unwrap_or(code.len());
+
+    if is_keyword(tokens.get(i), "package") {
+        // replace the declaration along with its annotations (they are only allowed in package-info.java)
+        let end = tokens[i..]
+            .iter()
+            .find(|t| is_symbol(Some(t), ";"))
+            .map(|t| t.span.end)
+            .unwrap_or(code.len());
+        let newlines = code[start..end].matches('\n').count();
+
+        format!(
+            "{}{}{}{}",
+            &code[..start],
+            declaration,
+            "\n".repeat(newlines),
+            &code[end..]
+        )
+    } else {
+        // insert on the line of the first declaration
+        format!("{}{} {}", &code[..start], declaration, &code[start..])
+    }
+}
+
+fn finish_implemented_type(last_identifier: &mut Option<&str>, implementing: bool, res: &mut bool) {
+    if implementing && *last_identifier == Some("Player") {
+        *res = true;
+    }
+    *last_identifier = None;
+}
+
+/// Checks whether the `implements` clause in the type declaration header lists the `Player`
+fn header_implements_player(header: &[Token]) -> bool {
+    let mut angle_depth = 0usize;
+    let mut implementing = false;
+    let mut last_identifier = None;
+    let mut res = false;
+
+    for t in header {
+        match (t.kind, t.text) {
+            (TokenKind::Keyword, "implements") if angle_depth == 0 => implementing = true,
+            (TokenKind::Keyword, "extends") | (TokenKind::Identifier, "permits")
+                if angle_depth == 0 =>
+            {
+                finish_implemented_type(&mut last_identifier, implementing, &mut res);
+                implementing = false;
+            }
+            (TokenKind::Symbol, "<") => {
+                if angle_depth == 0 {
+                    finish_implemented_type(&mut last_identifier, implementing, &mut res);
+                }
+                angle_depth += 1;
+            }
+            (TokenKind::Symbol, ">") => angle_depth = angle_depth.saturating_sub(1),
+            (TokenKind::Symbol, ">>") => angle_depth = angle_depth.saturating_sub(2),
+            (TokenKind::Symbol, ">>>") => angle_depth = angle_depth.saturating_sub(3),
+            (TokenKind::Symbol, ",") if angle_depth == 0 => {
+                finish_implemented_type(&mut last_identifier, implementing, &mut res)
+            }
+            (TokenKind::Identifier, name) if angle_depth == 0 && implementing => {
+                last_identifier = Some(name)
+            }
+            _ => {}
+        }
+    }
+    finish_implemented_type(&mut last_identifier, implementing, &mut res);
+
+    res
+}
+
+/// Finds the top-level classes, interfaces, enums and records declared in the compilation unit
+pub fn top_level_types(code: &str) -> Vec<TypeDeclaration> {
+    let tokens = code_tokens(code);
+    let mut res = Vec::new();
+
+    let mut depth = 0usize;
+    // the first token of the current top-level declaration
+    let mut declaration_start = 0;
+    let mut i = 0;
+
+    while let Some(t) = tokens.get(i) {
+        let is_type_keyword = matches!(
+            (t.kind, t.text),
+            (TokenKind::Keyword, "class" | "interface" | "enum")
+                | (TokenKind::Identifier, "record")
+        );
+
+        if depth == 0 && is_type_keyword && is_identifier(tokens.get(i + 1)) {
+            let is_public = tokens[declaration_start..i]
+                .iter()
+                .any(|t| is_keyword(Some(t), "public"));
+
+            // the header lasts until the body
+            let mut paren_depth = 0usize;
+            let mut header_end = i + 2;
+            while let Some(t) = tokens.get(header_end) {
+                match t.text {
+                    "(" => paren_depth += 1,
+                    ")" => paren_depth = paren_depth.saturating_sub(1),
+                    "{" if paren_depth == 0 => break,
+                    _ => {}
+                }
+                header_end += 1;
+            }
+
+            res.push(TypeDeclaration {
+                name: tokens[i + 1].text.to_string(),
+                is_public,
+                implements_player: header_implements_player(&tokens[i + 2..header_end]),
+            });
+
+            i = header_end;
+            continue;
+        }
+
+        match (t.kind, t.text) {
+            (TokenKind::Symbol, "{") => depth += 1,
+            (TokenKind::Symbol, "}") => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    declaration_start = i + 1;
+                }
+            }
+            (TokenKind::Symbol, ";") if depth == 0 => declaration_start = i + 1,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn declaration(name: &str, is_public: bool, implements_player: bool) -> TypeDeclaration {
+        TypeDeclaration {
+            name: name.to_string(),
+            is_public,
+            implements_player,
+        }
+    }
+
+    #[test]
+    fn replaces_the_package() {
+        assert_eq!(
+            patch_package("package a.b;\nclass A {}\n", "player_0"),
+            "package player_0;\nclass A {}\n"
+        );
+    }
+
+    #[test]
+    fn keeps_the_comments_before_the_package() {
+        let code = "/* package fake; */\n// package other;\npackage a.b;\nclass A {}";
+        assert_eq!(
+            patch_package(code, "p"),
+            "/* package fake; */\n// package other;\npackage p;\nclass A {}"
+        );
+    }
+
+    #[test]
+    fn preserves_the_lines_of_a_multiline_package() {
+        let code = "@Deprecated\npackage\n  a\n  .b\n;  class A {}\nclass B {}";
+        let patched = patch_package(code, "p");
+        assert_eq!(patched, "package p;\n\n\n\n  class A {}\nclass B {}");
+        assert_eq!(patched.lines().count(), code.lines().count());
+    }
+
+    #[test]
+    fn inserts_a_missing_package() {
+        assert_eq!(
+            patch_package("// header\n\nimport java.util.*;\nclass A {}", "p"),
+            "// header\n\npackage p; import java.util.*;\nclass A {}"
+        );
+        // an annotation on a type is not a package annotation
+        assert_eq!(
+            patch_package("@SuppressWarnings(\"all\") class A {}", "p"),
+            "package p; @SuppressWarnings(\"all\") class A {}"
+        );
+        assert_eq!(patch_package("", "p"), "package p; ");
+    }
+
+    #[test]
+    fn strips_the_byte_order_mark() {
+        assert_eq!(
+            patch_package("\u{feff}package a;\nclass A {}", "p"),
+            "package p;\nclass A {}"
+        );
+    }
+
+    #[test]
+    fn finds_the_public_player() {
+        let code = r#"
+package a;
+
+import gametheory.assignment2.Player;
+
+// public class Fake implements Player {}
+class Helper implements Comparable<Helper> {
+    class Inner implements Player {}
+}
+
+@SuppressWarnings("unused")
+public final class Strat extends Object implements Comparable<Map<String, List<Player>>>, gametheory.assignment2.Player {
+    String s = "public class Str implements Player";
+}
+
+interface Marker extends Player {}
+enum E implements Player { A }
+record R(int x) implements Player {}
+"#;
+        assert_eq!(
+            top_level_types(code),
+            vec![
+                declaration("Helper", false, false),
+                declaration("Strat", true, true),
+                declaration("Marker", false, false),
+                declaration("E", false, true),
+                declaration("R", false, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn player_only_in_type_arguments() {
+        assert_eq!(
+            top_level_types("public class A implements Supplier<Player>, Runnable {}"),
+            vec![declaration("A", true, false)]
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use futures_util::stream::StreamExt;
+use serde::{Deserialize, Serialize};
+use shiplift::{Docker, PullOptions};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use tracing::{debug, info};
+
+/// Major version of the JDK (8, 11, 17...)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct JdkVersion(pub u32);
+
+impl Display for JdkVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Docker images providing each of the supported JDK versions
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct JdkImages(BTreeMap<JdkVersion, String>);
+
+impl Default for JdkImages {
+    fn default() -> Self {
+        JdkImages(BTreeMap::from([
+            (JdkVersion(8), "openjdk:8-alpine".to_string()),
+            (JdkVersion(11), "eclipse-temurin:11-jdk-alpine".to_string()),
+            (JdkVersion(17), "eclipse-temurin:17-jdk-alpine".to_string()),
+            (JdkVersion(21), "eclipse-temurin:21-jdk-alpine".to_string()),
+        ]))
+    }
+}
+
+impl JdkImages {
+    pub fn image(&self, version: JdkVersion) -> Result<&str, anyhow::Error> {
+        self.0
+            .get(&version)
+            .map(|s| s.as_str())
+            .ok_or_else(|| crate::error::Error::UnsupportedJdk(version).into())
+    }
+
+    pub fn versions(&self) -> impl Iterator<Item = JdkVersion> + '_ {
+        self.0.keys().copied()
+    }
+
+    pub async fn pull(&self, docker: &Docker) -> Result<(), anyhow::Error> {
+        for image in self.0.values() {
+            info!("Gonna pull image {image}");
+
+            let mut stream = docker
+                .images()
+                .pull(&PullOptions::builder().image(image).build());
+
+            while let Some(pull_result) = stream.next().await {
+                debug!("Pull message: {}", pull_result?);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+//! A lenient lexer for the Java source code.
+//!
+//! It never fails: malformed input (unterminated literals or comments, stray characters)
+//! still produces tokens, so it can be used on the code that does not compile.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Identifier,
+    Keyword,
+    Number,
+    String,
+    Char,
+    Symbol,
+    Comment,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    /// Byte range of the token in the source
+    pub span: Range<usize>,
+}
+
+const KEYWORDS: &[&str] = &[
+    "abstract",
+    "assert",
+    "boolean",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extends",
+    "false",
+    "final",
+    "finally",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "implements",
+    "import",
+    "instanceof",
+    "int",
+    "interface",
+    "long",
+    "native",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "short",
+    "static",
+    "strictfp",
+    "super",
+    "switch",
+    "synchronized",
+    "this",
+    "throw",
+    "throws",
+    "transient",
+    "true",
+    "try",
+    "void",
+    "volatile",
+    "while",
+];
+
+// longest first, so that the first match is the longest one
+const SYMBOLS: &[&str] = &[
+    ">>>=", "<<=", ">>=", ">>>", "...", "->", "::", "++", "--", "&&", "||", "==", "!=", "<=", ">=",
+    "+=", "-=", "*=", "/=", "&=", "|=", "^=", "%=", "<<", ">>", "(", ")", "{", "}", "[", "]", ";",
+    ",", ".", "@", "=", ">", "<", "!", "~", "?", ":", "+", "-", "*", "/", "&", "|", "^", "%",
+];
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '$'
+}
+
+fn is_identifier_part(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+struct Lexer<'a> {
+    code: &'a str,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn rest(&self) -> &'a str {
+        &self.code[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat_while(&mut self, mut pred: impl FnMut(char) -> bool) {
+        while let Some(c) = self.peek() {
+            if !pred(c) {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    fn skip_until(&mut self, terminator: &str) {
+        match self.rest().find(terminator) {
+            Some(i) => self.pos += i + terminator.len(),
+            None => self.pos = self.code.len(),
+        }
+    }
+
+    fn quoted(&mut self, quote: char) {
+        // the opening quote is already consumed
+        while let Some(c) = self.bump() {
+            match c {
+                '\\' => {
+                    self.bump();
+                }
+                '\n' => break,
+                c if c == quote => break,
+                _ => {}
+            }
+        }
+    }
+
+    fn number(&mut self) {
+        let hex = self.rest().starts_with("0x") || self.rest().starts_with("0X");
+        let mut prev = '\0';
+        while let Some(c) = self.peek() {
+            let exponent_sign = (c == '+' || c == '-')
+                && (matches!(prev, 'p' | 'P') || (!hex && matches!(prev, 'e' | 'E')));
+            if !(c.is_ascii_alphanumeric() || c == '_' || c == '.' || exponent_sign) {
+                break;
+            }
+            prev = c;
+            self.bump();
+        }
+    }
+
+    fn next_token(&mut self) -> Option<Token<'a>> {
+        // whitespace (and a byte order mark)
+        self.eat_while(|c| c.is_whitespace() || c == '\u{feff}');
+
+        let start = self.pos;
+        let rest = self.rest();
+        let c = self.peek()?;
+
+        let kind = if rest.starts_with("//") {
+            self.eat_while(|c| c != '\n');
+            TokenKind::Comment
+        } else if rest.starts_with("/*") {
+            self.pos += 2;
+            self.skip_until("*/");
+            TokenKind::Comment
+        } else if rest.starts_with("\"\"\"") {
+            // a text block
+            self.pos += 3;
+            loop {
+                match self.bump() {
+                    None => break,
+                    Some('\\') => {
+                        self.bump();
+                    }
+                    Some('"') if self.rest().starts_with("\"\"") => {
+                        self.pos += 2;
+                        break;
+                    }
+                    Some(_) => {}
+                }
+            }
+            TokenKind::String
+        } else if c == '"' {
+            self.bump();
+            self.quoted('"');
+            TokenKind::String
+        } else if c == '\'' {
+            self.bump();
+            self.quoted('\'');
+            TokenKind::Char
+        } else if c.is_ascii_digit()
+            || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            self.number();
+            TokenKind::Number
+        } else if is_identifier_start(c) {
+            self.eat_while(is_identifier_part);
+            if KEYWORDS.contains(&&self.code[start..self.pos]) {
+                TokenKind::Keyword
+            } else {
+                TokenKind::Identifier
+            }
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            self.pos += symbol.len();
+            TokenKind::Symbol
+        } else {
+            self.bump();
+            TokenKind::Unknown
+        };
+
+        Some(Token {
+            kind,
+            text: &self.code[start..self.pos],
+            span: start..self.pos,
+        })
+    }
+}
+
+/// Splits the source code into tokens, skipping the whitespace
+pub fn tokenize(code: &str) -> Vec<Token<'_>> {
+    let mut lexer = Lexer { code, pos: 0 };
+
+    std::iter::from_fn(|| lexer.next_token()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(code: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(code)
+            .into_iter()
+            .map(|t| (t.kind, t.text))
+            .collect()
+    }
+
+    #[test]
+    fn keywords_identifiers_and_symbols() {
+        assert_eq!(
+            kinds("public int move(int x) { return x >>>= 2; }"),
+            vec![
+                (TokenKind::Keyword, "public"),
+                (TokenKind::Keyword, "int"),
+                (TokenKind::Identifier, "move"),
+                (TokenKind::Symbol, "("),
+                (TokenKind::Keyword, "int"),
+                (TokenKind::Identifier, "x"),
+                (TokenKind::Symbol, ")"),
+                (TokenKind::Symbol, "{"),
+                (TokenKind::Keyword, "return"),
+                (TokenKind::Identifier, "x"),
+                (TokenKind::Symbol, ">>>="),
+                (TokenKind::Number, "2"),
+                (TokenKind::Symbol, ";"),
+                (TokenKind::Symbol, "}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_and_literals() {
+        assert_eq!(
+            kinds("// line\n/* block\n */ \"a \\\" b\" 'c' '\\n' \"\"\"\ntext \"\" block\"\"\""),
+            vec![
+                (TokenKind::Comment, "// line"),
+                (TokenKind::Comment, "/* block\n */"),
+                (TokenKind::String, "\"a \\\" b\""),
+                (TokenKind::Char, "'c'"),
+                (TokenKind::Char, "'\\n'"),
+                (TokenKind::String, "\"\"\"\ntext \"\" block\"\"\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(
+            kinds("1_000L 0x1F .5 1.5e-3 0x1p+2 a-1"),
+            vec![
+                (TokenKind::Number, "1_000L"),
+                (TokenKind::Number, "0x1F"),
+                (TokenKind::Number, ".5"),
+                (TokenKind::Number, "1.5e-3"),
+                (TokenKind::Number, "0x1p+2"),
+                (TokenKind::Identifier, "a"),
+                (TokenKind::Symbol, "-"),
+                (TokenKind::Number, "1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_input() {
+        assert_eq!(
+            kinds("\"open\nx /* never closed"),
+            vec![
+                (TokenKind::String, "\"open\n"),
+                (TokenKind::Identifier, "x"),
+                (TokenKind::Comment, "/* never closed"),
+            ]
+        );
+        assert_eq!(kinds("#"), vec![(TokenKind::Unknown, "#")]);
+    }
+
+    #[test]
+    fn spans_skip_the_byte_order_mark() {
+        let code = "\u{feff}package été;";
+        let tokens = tokenize(code);
+        assert_eq!(tokens[0].span, 3..10);
+        assert_eq!(tokens[1].kind, TokenKind::Identifier);
+        assert_eq!(&code[tokens[1].span.clone()], "été");
+        assert_eq!(tokens[2].span, 16..17);
+    }
+}
@@ -2,8 +2,12 @@ use crate::compiler::JavaCompiler;
 use crate::runner::Runner;
 
 pub mod compiler;
+pub mod diagnostics;
 pub mod docker_util;
 pub mod error;
+pub mod java_source;
+pub mod jdk;
+pub mod lexer;
 pub mod matchmaker;
 pub mod runner;
 
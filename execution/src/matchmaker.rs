@@ -1,30 +1,46 @@
-use crate::compiler::JavaProgram;
-use crate::error::Error::FixtureFailure;
+use crate::compiler::{CompiledJavaProgram, JavaProgram, SourceOrigin, APP_DIR};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::error::Error;
+use crate::error::Error::{CompilationError, FixtureFailure, InvalidSource};
+use crate::java_source::{patch_package, top_level_types};
+use crate::jdk::JdkVersion;
 use crate::ExecutionState;
-use lazy_static::lazy_static;
-use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::Arc;
 use tracing::{info, instrument};
 
 const NAMESPACE: &str = "gametheory.assignment2";
-lazy_static! {
-    static ref PLAYER_ID_REGEX: Regex =
-        Regex::new(r"^gametheory\.assignment2\.player_([^.]+)\.Strat$").unwrap();
+
+fn is_java_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
 }
 
-fn patch_package(code: &str, package_name: &str) -> String {
-    lazy_static! {
-        static ref PACKAGE_REGEX: Regex =
-            Regex::new(r"^\s*package\s+([a-z][a-z0-9_]*(\.[a-z0-9_]+)*[0-9a-z_])\s*;").unwrap();
-    }
+/// A single source file of a player
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    /// Name of the file, the top-level class in it should be named the same
+    pub name: String,
+    pub code: String,
+}
 
-    let package_regex: &Regex = &PACKAGE_REGEX;
+impl SourceFile {
+    pub fn new(name: String, code: String) -> Self {
+        Self { name, code }
+    }
 
-    package_regex
-        .replace(code, format!("package {package_name};"))
-        .to_string()
+    fn class_name(&self) -> Result<&str, anyhow::Error> {
+        match self.name.strip_suffix(".java") {
+            Some(n) if is_java_identifier(n) => Ok(n),
+            _ => Err(InvalidSource(format!(
+                "File name {} is not a valid name for a java source file",
+                self.name
+            ))
+            .into()),
+        }
+    }
 }
 
 macro_rules! include_java {
@@ -37,8 +53,52 @@ macro_rules! include_java {
     };
 }
 
-pub fn make_match_program(players: &HashMap<String, String>) -> Result<JavaProgram, anyhow::Error> {
-    let mut program = JavaProgram::new();
+/// A program running the fixture over a set of players.
+///
+/// Each player is compiled into its own class root, which the fixture loads with a separate classloader
+#[derive(Debug)]
+pub struct MatchProgram {
+    program: JavaProgram,
+    players: Vec<MatchPlayer>,
+}
+
+#[derive(Debug)]
+struct MatchPlayer {
+    name: String,
+    root: String,
+    class_name: String,
+}
+
+impl MatchProgram {
+    /// Leaves the players out of the matches
+    fn remove_players(&mut self, names: &BTreeSet<String>) {
+        self.players.retain(|p| !names.contains(&p.name));
+        self.program.remove_owners(names);
+    }
+
+    /// Name, class root and class name of each player, as separate arguments
+    fn fixture_args(&self) -> Vec<String> {
+        self.players
+            .iter()
+            .flat_map(|p| {
+                [
+                    p.name.clone(),
+                    format!("{APP_DIR}/{}", p.root),
+                    p.class_name.clone(),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Makes the program playing the players, each given by their sources and the JDK to compile them for.
+///
+/// The whole program is compiled and run with `jdk`, so it should not be older than the JDKs of the players
+pub fn make_match_program(
+    players: &HashMap<String, (Vec<SourceFile>, JdkVersion)>,
+    jdk: JdkVersion,
+) -> Result<MatchProgram, anyhow::Error> {
+    let mut program = JavaProgram::new(jdk);
     // the code that does the match-making and stuff
     program.push_class(
         format!("{NAMESPACE}.Fixture"),
@@ -50,42 +110,114 @@ pub fn make_match_program(players: &HashMap<String, String>) -> Result<JavaProgr
         include_java!("Player.java").to_string(),
     );
 
-    for (id, code) in players {
-        let class_name = format!("{NAMESPACE}.player_{id}.Strat");
-        let package_name = &class_name[..class_name.rfind('.').unwrap()];
+    let mut match_players = Vec::new();
+
+    // the ids are not valid java identifiers in general, the packages are numbered instead
+    for (index, (id, (files, player_jdk))) in players.iter().enumerate() {
+        let package_name = format!("{NAMESPACE}.player_{index}");
+        let root = format!("players/{index}");
+
+        let mut player_classes = Vec::new();
+
+        for file in files {
+            let class_name = format!("{package_name}.{}", file.class_name()?);
 
-        let code = patch_package(code, package_name);
+            player_classes.extend(
+                top_level_types(&file.code)
+                    .into_iter()
+                    .filter(|t| t.implements_player),
+            );
 
-        program.push_class(class_name, code)
+            let code = patch_package(&file.code, &package_name);
+            program.push_class_at(
+                root.clone(),
+                *player_jdk,
+                class_name,
+                code,
+                SourceOrigin {
+                    owner: id.clone(),
+                    file_name: file.name.clone(),
+                },
+            );
+        }
+
+        let class_name = match player_classes.as_slice() {
+            [c] if c.is_public => format!("{package_name}.{}", c.name),
+            [c] => {
+                return Err(InvalidSource(format!(
+                    "Class {} implementing gametheory.assignment2.Player should be public",
+                    c.name
+                ))
+                .into())
+            }
+            [] => {
+                return Err(InvalidSource(format!(
+                    "No class implementing gametheory.assignment2.Player found in the code of {id}"
+                ))
+                .into())
+            }
+            _ => {
+                return Err(InvalidSource(format!(
+                    "Several classes implementing gametheory.assignment2.Player found in the code of {id}: {}",
+                    player_classes.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ")
+                ))
+                .into())
+            }
+        };
+
+        match_players.push(MatchPlayer {
+            name: id.clone(),
+            root,
+            class_name,
+        });
     }
 
-    Ok(program)
-}
-
-pub fn match_with_dummy_strats(id: String, code: String) -> Result<JavaProgram, anyhow::Error> {
-    make_match_program(&HashMap::from([
-        (id, code),
-        (
-            "strat1".to_string(),
-            include_java!("strat1/Strat.java").to_string(),
-        ),
-        (
-            "strat2".to_string(),
-            include_java!("strat2/Strat.java").to_string(),
-        ),
-        (
-            "stratmirror".to_string(),
-            include_java!("stratmirror/Strat.java").to_string(),
-        ),
-        (
-            "stratrnd".to_string(),
-            include_java!("stratrnd/Strat.java").to_string(),
-        ),
-        (
-            "stratrnd2".to_string(),
-            include_java!("stratrnd2/Strat.java").to_string(),
-        ),
-    ]))
+    Ok(MatchProgram {
+        program,
+        players: match_players,
+    })
+}
+
+/// The bundled strategies each submission is validated against, as (name, source code) pairs
+pub const DUMMY_STRATS: &[(&str, &str)] = &[
+    ("strat1", include_java!("strat1/Strat.java")),
+    ("strat2", include_java!("strat2/Strat.java")),
+    ("stratmirror", include_java!("stratmirror/Strat.java")),
+    ("stratrnd", include_java!("stratrnd/Strat.java")),
+    ("stratrnd2", include_java!("stratrnd2/Strat.java")),
+];
+
+/// Sources of a strategy submitted as a single file.
+///
+/// The file is named after the public top-level type declared in it, as javac requires
+pub fn single_file_sources(code: String) -> Vec<SourceFile> {
+    let name = top_level_types(&code)
+        .into_iter()
+        .find(|t| t.is_public)
+        .map(|t| t.name)
+        .unwrap_or_else(|| "Strat".to_string());
+
+    vec![SourceFile::new(format!("{name}.java"), code)]
+}
+
+pub fn match_with_dummy_strats(
+    id: String,
+    files: Vec<SourceFile>,
+    jdk: JdkVersion,
+) -> Result<MatchProgram, anyhow::Error> {
+    make_match_program(
+        &DUMMY_STRATS
+            .iter()
+            .map(|(name, code)| {
+                (
+                    name.to_string(),
+                    (single_file_sources(code.to_string()), jdk),
+                )
+            })
+            .chain(std::iter::once((id, (files, jdk))))
+            .collect(),
+        jdk,
+    )
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -128,8 +260,6 @@ mod raw_json {
 fn parse_round_result(val: &str) -> Result<RoundResult, anyhow::Error> {
     let raw: raw_json::RoundResult = serde_json::from_str(val)?;
 
-    let player_id_regex: &Regex = &PLAYER_ID_REGEX;
-
     let mut res = RoundResult(Vec::new());
 
     let conv_player_result = |p: raw_json::PlayerResult| {
@@ -142,7 +272,7 @@ fn parse_round_result(val: &str) -> Result<RoundResult, anyhow::Error> {
         PlayerResult {
             outcome,
             moves: p.moves,
-            player_name: player_id_regex.replace(&p.player_name, "$1").to_string(),
+            player_name: p.player_name,
         }
     };
 
@@ -158,17 +288,30 @@ fn parse_round_result(val: &str) -> Result<RoundResult, anyhow::Error> {
 }
 
 #[instrument(skip_all)]
-pub async fn run_matched_program(
-    execution_state: Arc<ExecutionState>,
-    program: &JavaProgram,
-) -> Result<RoundResult, anyhow::Error> {
-    let program = execution_state.compiler.compile(program).await?;
+pub async fn compile_matched_program(
+    execution_state: &ExecutionState,
+    program: &MatchProgram,
+) -> Result<CompiledJavaProgram, anyhow::Error> {
+    let compiled = execution_state.compiler.compile(&program.program).await?;
+
+    info!("Compiled {compiled:?}");
 
-    info!("Compiled {program:?}");
+    Ok(compiled)
+}
 
+#[instrument(skip_all)]
+pub async fn run_compiled_match(
+    execution_state: &ExecutionState,
+    program: &MatchProgram,
+    compiled: &CompiledJavaProgram,
+) -> Result<RoundResult, anyhow::Error> {
     let (exit, out, err) = execution_state
         .runner
-        .run_java(&program, &format!("{NAMESPACE}.Fixture"))
+        .run_java(
+            compiled,
+            &format!("{NAMESPACE}.Fixture"),
+            &program.fixture_args(),
+        )
         .await?;
 
     if exit.status_code != 0 {
@@ -190,3 +333,59 @@ pub async fn run_matched_program(
 
     Ok(parse)
 }
+
+/// Compiles the program, leaving out the players whose code does not compile.
+///
+/// Returns the compiled program along with the diagnostics of the players left out
+#[instrument(skip_all)]
+pub async fn compile_available_players(
+    execution_state: &ExecutionState,
+    program: &mut MatchProgram,
+) -> Result<(CompiledJavaProgram, BTreeMap<String, Vec<Diagnostic>>), anyhow::Error> {
+    let mut failed = BTreeMap::new();
+
+    loop {
+        let err = match compile_matched_program(execution_state, program).await {
+            Ok(compiled) => return Ok((compiled, failed)),
+            Err(e) => e,
+        };
+        let diagnostics = match err.downcast_ref::<Error>() {
+            Some(CompilationError(_, diagnostics)) => diagnostics,
+            _ => return Err(err),
+        };
+
+        let failing: BTreeSet<String> = diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .filter_map(|d| d.owner.clone())
+            .collect();
+        // an error in the shared code, there is nobody to leave out
+        if failing.is_empty() {
+            return Err(err);
+        }
+
+        for name in &failing {
+            let own = diagnostics
+                .iter()
+                .filter(|d| d.owner.as_ref() == Some(name))
+                .cloned()
+                .collect();
+            failed.insert(name.clone(), own);
+        }
+        program.remove_players(&failing);
+    }
+}
+
+/// Plays the program with the players that compile.
+///
+/// Returns the result along with the diagnostics of the players left out
+pub async fn run_matched_program(
+    execution_state: Arc<ExecutionState>,
+    mut program: MatchProgram,
+) -> Result<(RoundResult, BTreeMap<String, Vec<Diagnostic>>), anyhow::Error> {
+    let (compiled, failed) = compile_available_players(&execution_state, &mut program).await?;
+
+    let res = run_compiled_match(&execution_state, &program, &compiled).await?;
+
+    Ok((res, failed))
+}
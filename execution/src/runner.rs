@@ -1,46 +1,33 @@
-use crate::compiler::CompiledJavaProgram;
+use crate::compiler::{CompiledJavaProgram, APP_DIR};
 use crate::docker_util::run_container;
-use futures_util::stream::StreamExt;
+use crate::jdk::JdkImages;
 use shiplift::rep::Exit;
-use shiplift::{ContainerOptions, Docker, PullOptions};
+use shiplift::{ContainerOptions, Docker};
 use std::error::Error;
 use std::fmt::{Debug, Formatter};
 use std::time::Duration;
-use tracing::{debug, info, instrument, trace};
+use tracing::{info, instrument, trace};
 
-const IMAGE_NAME: &str = "openjdk:8-alpine";
 const TIMEOUT: Duration = Duration::from_secs(40);
 
 pub struct Runner {
     docker: Docker,
-    image_name: String,
+    images: JdkImages,
 }
 
 impl Debug for Runner {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Runner")
-            .field("image_name", &self.image_name)
+            .field("images", &self.images)
             .finish()
     }
 }
 
 impl Runner {
-    pub async fn new(docker: Docker) -> Result<Self, Box<dyn Error>> {
-        info!("Gonna pull image {IMAGE_NAME}");
-        {
-            let mut stream = docker
-                .images()
-                .pull(&PullOptions::builder().image(IMAGE_NAME).build());
+    pub async fn new(docker: Docker, images: JdkImages) -> Result<Self, Box<dyn Error>> {
+        images.pull(&docker).await?;
 
-            while let Some(pull_result) = stream.next().await {
-                debug!("Pull message: {}", pull_result?);
-            }
-        }
-
-        Ok(Self {
-            docker,
-            image_name: IMAGE_NAME.to_string(),
-        })
+        Ok(Self { docker, images })
     }
 
     #[instrument]
@@ -48,18 +35,23 @@ impl Runner {
         &self,
         program: &CompiledJavaProgram,
         main_class: &str,
+        args: &[String],
     ) -> Result<(Exit, String, String), anyhow::Error> {
         info!(
-            "Running java program {:?} with class {}",
+            "Running java program {:?} with class {} on JDK {}",
             program.path(),
-            main_class
+            main_class,
+            program.jdk()
         );
 
-        let mounts = vec![format!("{}:/app", program.path().to_str().unwrap())];
-        let cmd = vec!["java", "-cp", "/app", main_class];
+        let image_name = self.images.image(program.jdk())?;
+
+        let mounts = [format!("{}:{APP_DIR}", program.path().to_str().unwrap())];
+        let mut cmd = vec!["java", "-cp", APP_DIR, main_class];
+        cmd.extend(args.iter().map(|s| s.as_str()));
 
         trace!("Creating runner container...");
-        let container = ContainerOptions::builder(&self.image_name)
+        let container = ContainerOptions::builder(image_name)
             .volumes(mounts.iter().map(|s| s.as_str()).collect())
             .cmd(cmd)
             .network_mode("none")
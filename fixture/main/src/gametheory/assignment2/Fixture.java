@@ -1,82 +1,88 @@
 package gametheory.assignment2;
 
-import java.io.BufferedReader;
 import java.io.File;
-import java.io.InputStream;
-import java.io.InputStreamReader;
 import java.lang.reflect.Constructor;
+import java.net.URL;
+import java.net.URLClassLoader;
 import java.util.ArrayList;
 import java.util.Arrays;
-import java.util.HashSet;
-import java.util.Set;
+import java.util.Objects;
 import java.util.concurrent.*;
 import java.util.function.Supplier;
 import java.util.stream.Collectors;
+import java.util.stream.IntStream;
 
 public class Fixture {
-    private static final String PACKAGE_NAME = "gametheory.assignment2";
     private static final int MAX_MOVES = 100;
 
-    private Set<Class<?>> findAllClassesInPackage(String packag3) {
-        InputStream stream = ClassLoader.getSystemClassLoader()
-                .getResourceAsStream(packag3.replaceAll("[.]", "/"));
-        if (stream == null)
-            return new HashSet<>();
-        BufferedReader reader = new BufferedReader(new InputStreamReader(stream));
-        return reader.lines()
-                .filter(line -> line.endsWith(".class"))
-                .map(n -> getClass(n, packag3))
-                .collect(Collectors.toSet());
-    }
+    /**
+     * Exposes JDK classes and the {@link Player} interface, but nothing else from the fixture classpath.
+     * Each player's classes are loaded by a separate child of this loader, so players can't see each other.
+     */
+    static class SharedClassLoader extends ClassLoader {
+        SharedClassLoader() {
+            super(ClassLoader.getSystemClassLoader().getParent());
+        }
 
-    private Set<Class<?>> findAllClassesUsingClassLoader(String[] packages) {
-        return Arrays.stream(packages)
-                .map(r -> findAllClassesInPackage(PACKAGE_NAME + "." + r))
-                .reduce(new HashSet<>(), (a, e) -> { a.addAll(e); return a; });
+        @Override
+        protected Class<?> findClass(String name) throws ClassNotFoundException {
+            if (name.equals(Player.class.getName()))
+                return Player.class;
+            throw new ClassNotFoundException(name);
+        }
     }
 
-    static Class<?> getClass(String className, String packag3) {
+    private final ClassLoader sharedClassLoader = new SharedClassLoader();
+
+    private Supplier<PlayerWrapper> getPlayer(String name, String classRoot, String className) {
+        Class<?> clazz;
         try {
-            return Class.forName(packag3 + "."
-                    + className.substring(0, className.lastIndexOf('.')));
-        } catch (ClassNotFoundException e) {
+            URLClassLoader loader = new URLClassLoader(
+                    new URL[] { new File(classRoot).toURI().toURL() }, sharedClassLoader);
+            clazz = loader.loadClass(className);
+        } catch (Throwable e) {
+            System.err.println("Cannot load player class " + className + " for " + name + ":\n" + e);
+            return null;
+        }
+
+        if (!Player.class.isAssignableFrom(clazz)) {
+            System.err.println("Class " + className + " of " + name + " does not implement " + Player.class.getName());
+            return null;
+        }
 
-            // handle the exception
+        Constructor<?> constructor;
+        try {
+            constructor = clazz.getConstructor();
+        } catch (NoSuchMethodException e) {
+            System.err.println("Cannot find constructor for player class " + clazz);
+            System.err.println("Does your class has constructor?");
+            System.err.println("Is it public?");
+            System.err.println("Is the class itself public?");
+            throw new RuntimeException(e);
         }
-        return null;
+
+        return () -> {
+            Player player;
+            try {
+                player = (Player) constructor.newInstance();
+            } catch (Throwable e) {
+                System.err.println("Cannot construct player class " + clazz);
+                throw new RuntimeException(e);
+            }
+
+            return new PlayerWrapper(name, player);
+        };
     }
 
-    private Supplier<PlayerWrapper>[] getPlayers(String[] packages) {
-        Set<Class<?>> classes = findAllClassesUsingClassLoader(packages);
-        Class<?> playerInterface = Player.class;
-
-        Supplier<?>[] suppliers = classes.stream().filter(clazz -> Arrays.stream(clazz.getInterfaces()).anyMatch(p -> p == playerInterface))
-                .map(clazz -> {
-                    Constructor<?> constructor;
-                    try {
-                        constructor = clazz.getConstructor();
-                    } catch (NoSuchMethodException e) {
-                        System.err.println("Cannot find constructor for player class " + clazz);
-                        System.err.println("Does your class has constructor?");
-                        System.err.println("Is it public?");
-                        System.err.println("Is the class itself public?");
-                        throw new RuntimeException(e);
-                    }
-
-                    return (Supplier<PlayerWrapper>) () -> {
-                        Player player;
-                        try {
-                            player = (Player) constructor.newInstance();
-                        } catch (Throwable e) {
-                            System.err.println("Cannot construct player class " + clazz);
-                            throw new RuntimeException(e);
-                        }
-
-                        String name = clazz.getCanonicalName();
-
-                        return new PlayerWrapper(name, player);
-                    };
-                }).toArray(Supplier[]::new);
+    // each player is given by three arguments: name, class root and class name
+    // (the names are user ids and may contain any character, so they are not joined into one)
+    private Supplier<PlayerWrapper>[] getPlayers(String[] args) {
+        if (args.length % 3 != 0)
+            throw new IllegalArgumentException("Expected name, class root and class name for each player");
+        Supplier<?>[] suppliers = IntStream.range(0, args.length / 3)
+                .mapToObj(i -> getPlayer(args[3 * i], args[3 * i + 1], args[3 * i + 2]))
+                .filter(Objects::nonNull)
+                .toArray(Supplier[]::new);
         return (Supplier<PlayerWrapper>[])suppliers;
     }
 
@@ -257,21 +263,7 @@ public class Fixture {
     }
 
     void realMain(String[] args) {
-        File classFile = new File(Fixture.class.getProtectionDomain().getCodeSource().getLocation().getPath() + "/"
-                + PACKAGE_NAME.replace(".", "/"));
-
-        String[] packages = Arrays.stream(classFile.listFiles()).filter(f -> f.isDirectory()).map(f -> f.getName()).toArray(String[]::new);
-
-//        String[] packages = new String[] {
-//                "strat1",
-//                "strat2",
-//                "stratmirror",
-//                "stratrnd",
-//                "stratrnd2",
-//
-//        };
-
-        Supplier<PlayerWrapper>[] players = getPlayers(packages);
+        Supplier<PlayerWrapper>[] players = getPlayers(args);
 
         ArrayList<Match> matches = new ArrayList<>();
         for (int i = 1; i < players.length; i++)
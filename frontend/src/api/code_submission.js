@@ -9,24 +9,48 @@ export default {
     });
   },
 
-  async create(data) {
+  async jdks() {
+    return axios.get("/jdks");
+  },
+
+  async create(data, jdk) {
     // eslint-disable-next-line
     return await axios
       .post("/submit", data, {
         withCredentials: true,
+        params: { jdk: jdk },
       })
-      // eslint-disable-next-line
       .then((r) => {
-        return { status: true, error: undefined };
+        return { job_id: r.data.job_id, error: undefined };
       })
       .catch(function (error) {
         if (error.response) {
           console.log(error.response.data);
           console.log(error.response.status);
-          console.log(error.response.headers);
-          return { status: false, error: error.response.data[1] };
+          return { job_id: undefined, error: error.response.data };
         }
-        return { status: false, error: error.message };
+        return { job_id: undefined, error: error.message };
       });
   },
+
+  async job(id) {
+    return axios.get("/jobs/" + id, {
+      withCredentials: true,
+    });
+  },
+
+  // polls the validation job until it is finished
+  async waitForJob(id, onStatus) {
+    for (;;) {
+      let job = (await this.job(id)).data;
+      onStatus(job.status);
+      if (job.status == "done") {
+        return { status: job.report.valid, error: job.report.message };
+      }
+      if (job.status == "failed") {
+        return { status: false, error: job.error };
+      }
+      await new Promise((resolve) => setTimeout(resolve, 1000));
+    }
+  },
 };
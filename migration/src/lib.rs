@@ -1,13 +1,73 @@
 pub use sea_schema::migration::migrator::MigratorTrait;
 pub use sea_schema::migration::*;
 
+use entity::sea_orm::{DbBackend, Statement};
+
 mod m20220101_000001_create_table;
+mod m20220322_000002_add_submission_files;
+mod m20220323_000003_add_submission_jdk_version;
+mod m20220324_000004_add_submission_report;
+mod m20220325_000005_create_users;
+mod m20220326_000006_add_user_sparring_partner;
+mod m20220327_000007_create_scoreboard_entries;
+mod m20220328_000008_normalize_round_results;
+mod m20220329_000009_add_round_final_tournament;
+mod m20220330_000010_add_late_submissions;
+mod m20220331_000011_create_house_strategies;
+mod m20220401_000012_add_bans_and_invalidation;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20220322_000002_add_submission_files::Migration),
+            Box::new(m20220323_000003_add_submission_jdk_version::Migration),
+            Box::new(m20220324_000004_add_submission_report::Migration),
+            Box::new(m20220325_000005_create_users::Migration),
+            Box::new(m20220326_000006_add_user_sparring_partner::Migration),
+            Box::new(m20220327_000007_create_scoreboard_entries::Migration),
+            Box::new(m20220328_000008_normalize_round_results::Migration),
+            Box::new(m20220329_000009_add_round_final_tournament::Migration),
+            Box::new(m20220330_000010_add_late_submissions::Migration),
+            Box::new(m20220331_000011_create_house_strategies::Migration),
+            Box::new(m20220401_000012_add_bans_and_invalidation::Migration),
+        ]
+    }
+}
+
+/// Drops the column of the table.
+///
+/// sea-query refuses to drop the columns on SQLite, but SQLite does it itself since 3.35
+pub(crate) async fn drop_column(
+    manager: &SchemaManager<'_>,
+    table: impl sea_query::Iden + 'static,
+    column: impl sea_query::Iden + 'static,
+) -> Result<(), DbErr> {
+    match manager.get_database_backend() {
+        DbBackend::Sqlite => {
+            let sql = format!(
+                r#"ALTER TABLE "{}" DROP COLUMN "{}""#,
+                table.to_string(),
+                column.to_string()
+            );
+            manager
+                .get_connection()
+                .execute(Statement::from_string(DbBackend::Sqlite, sql))
+                .await
+                .map(|_| ())
+        }
+        _ => {
+            manager
+                .alter_table(
+                    sea_query::Table::alter()
+                        .table(table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await
+        }
     }
 }
@@ -0,0 +1,32 @@
+use crate::drop_column;
+use entity::submission;
+use sea_schema::migration::{
+    sea_query::{self, *},
+    *,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220322_000002_add_submission_files"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                sea_query::Table::alter()
+                    .table(submission::Entity)
+                    .add_column(ColumnDef::new(submission::Column::Files).text())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_column(manager, submission::Entity, submission::Column::Files).await
+    }
+}
@@ -0,0 +1,38 @@
+use crate::drop_column;
+use entity::submission;
+use sea_schema::migration::{
+    sea_query::{self, *},
+    *,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220323_000003_add_submission_jdk_version"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // everything was running on JDK 8 before
+        manager
+            .alter_table(
+                sea_query::Table::alter()
+                    .table(submission::Entity)
+                    .add_column(
+                        ColumnDef::new(submission::Column::JdkVersion)
+                            .integer()
+                            .not_null()
+                            .default(8),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_column(manager, submission::Entity, submission::Column::JdkVersion).await
+    }
+}
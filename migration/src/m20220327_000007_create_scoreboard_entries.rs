@@ -0,0 +1,92 @@
+use entity::scoreboard_entry;
+use sea_schema::migration::{
+    sea_query::{self, *},
+    *,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220327_000007_create_scoreboard_entries"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                sea_query::Table::create()
+                    .table(scoreboard_entry::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(scoreboard_entry::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(scoreboard_entry::Column::RoundId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(scoreboard_entry::Column::Datetime)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(scoreboard_entry::Column::UserId)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(scoreboard_entry::Column::Rank)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(scoreboard_entry::Column::Score)
+                            .double()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                sea_query::Index::create()
+                    .name("IX_ScoreboardEntry_Datetime")
+                    .table(scoreboard_entry::Entity)
+                    .col(scoreboard_entry::Column::Datetime)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                sea_query::Index::create()
+                    .name("IX_ScoreboardEntry_UserId_Datetime")
+                    .table(scoreboard_entry::Entity)
+                    .col(scoreboard_entry::Column::UserId)
+                    .col(scoreboard_entry::Column::Datetime)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                sea_query::Table::drop()
+                    .table(scoreboard_entry::Entity)
+                    .to_owned(),
+            )
+            .await
+    }
+}
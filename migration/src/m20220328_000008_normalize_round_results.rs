@@ -0,0 +1,437 @@
+use entity::sea_orm::prelude::DateTimeUtc;
+use entity::sea_orm::{ConnectionTrait, FromQueryResult, IdenStatic, QueryResult};
+use entity::{match_result, player_result, round, round_result};
+use sea_schema::migration::{
+    sea_query::{self, *},
+    *,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220328_000008_normalize_round_results"
+    }
+}
+
+/// The stored format of `execution::matchmaker::MatchResult`
+#[derive(Serialize, Deserialize)]
+struct StoredMatch {
+    moves: u32,
+    player1: StoredPlayer,
+    player2: StoredPlayer,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredPlayer {
+    player_name: String,
+    outcome: Result<f64, String>,
+    moves: Vec<i32>,
+}
+
+fn json_error(e: serde_json::Error) -> DbErr {
+    DbErr::Custom(format!("Could not convert a stored round: {e}"))
+}
+
+async fn insert_player_result(
+    conn: &dyn ConnectionTrait,
+    round_id: i32,
+    match_id: i32,
+    participants: &BTreeMap<String, i32>,
+    (player, opponent, first): (&StoredPlayer, &StoredPlayer, bool),
+) -> Result<(), DbErr> {
+    // the blobs could have a player missing in the participants only if they were broken
+    let submission = |name: &str| participants.get(name).copied().unwrap_or(0);
+    let (score, error) = match &player.outcome {
+        Ok(score) => (Some(*score), None),
+        Err(e) => (None, Some(e.clone())),
+    };
+
+    let insert = Query::insert()
+        .into_table(player_result::Entity)
+        .columns([
+            player_result::Column::MatchId,
+            player_result::Column::RoundId,
+            player_result::Column::UserId,
+            player_result::Column::SubmissionId,
+            player_result::Column::OpponentUserId,
+            player_result::Column::OpponentSubmissionId,
+            player_result::Column::First,
+            player_result::Column::Score,
+            player_result::Column::Error,
+            player_result::Column::Moves,
+        ])
+        .values_panic([
+            match_id.into(),
+            round_id.into(),
+            player.player_name.clone().into(),
+            submission(&player.player_name).into(),
+            opponent.player_name.clone().into(),
+            submission(&opponent.player_name).into(),
+            first.into(),
+            score.into(),
+            error.into(),
+            serde_json::to_string(&player.moves).unwrap().into(),
+        ])
+        .to_owned();
+    conn.execute(conn.get_database_backend().build(&insert))
+        .await?;
+
+    Ok(())
+}
+
+/// Copies the rounds from the JSON blobs in `round_results` to the new tables, keeping their ids
+async fn convert_round_results(conn: &dyn ConnectionTrait) -> Result<(), DbErr> {
+    let backend = conn.get_database_backend();
+
+    let select = Query::select()
+        .columns([
+            round_result::Column::Id,
+            round_result::Column::Result,
+            round_result::Column::Participants,
+            round_result::Column::Datetime,
+        ])
+        .from(round_result::Entity)
+        .order_by(round_result::Column::Id, Order::Asc)
+        .to_owned();
+
+    for row in conn.query_all(backend.build(&select)).await? {
+        let rr = round_result::Model::from_query_result(&row, "")?;
+        let matches: Vec<StoredMatch> = serde_json::from_str(&rr.result).map_err(json_error)?;
+        let participants: BTreeMap<String, i32> =
+            serde_json::from_str(&rr.participants).map_err(json_error)?;
+
+        let insert = Query::insert()
+            .into_table(round::Entity)
+            .columns([round::Column::Id, round::Column::Datetime])
+            .values_panic([rr.id.into(), rr.datetime.into()])
+            .to_owned();
+        conn.execute(backend.build(&insert)).await?;
+
+        for m in &matches {
+            let insert = Query::insert()
+                .into_table(match_result::Entity)
+                .columns([match_result::Column::RoundId, match_result::Column::Moves])
+                .values_panic([rr.id.into(), (m.moves as i32).into()])
+                .to_owned();
+            let match_id = conn.execute(backend.build(&insert)).await?.last_insert_id() as i32;
+
+            for side in [
+                (&m.player1, &m.player2, true),
+                (&m.player2, &m.player1, false),
+            ] {
+                insert_player_result(conn, rr.id, match_id, &participants, side).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Puts the player results of a round back together into the JSON blobs of the matches and participants
+fn round_blobs(results: &[QueryResult]) -> Result<(String, String), DbErr> {
+    let mut participants = BTreeMap::new();
+    let mut match_moves = BTreeMap::new();
+    let mut match_players: BTreeMap<i32, Vec<(bool, StoredPlayer)>> = BTreeMap::new();
+
+    for r in results {
+        let user_id: String = r.try_get("", player_result::Column::UserId.as_str())?;
+        let submission_id: i32 = r.try_get("", player_result::Column::SubmissionId.as_str())?;
+        let score: Option<f64> = r.try_get("", player_result::Column::Score.as_str())?;
+        let error: Option<String> = r.try_get("", player_result::Column::Error.as_str())?;
+        let moves: String = r.try_get("", player_result::Column::Moves.as_str())?;
+        let match_id: i32 = r.try_get("", player_result::Column::MatchId.as_str())?;
+        let moves_played: i32 = r.try_get("", "match_moves")?;
+        let first: bool = r.try_get("", player_result::Column::First.as_str())?;
+
+        participants.insert(user_id.clone(), submission_id);
+        match_moves.insert(match_id, moves_played);
+        match_players.entry(match_id).or_default().push((
+            first,
+            StoredPlayer {
+                player_name: user_id,
+                outcome: match error {
+                    Some(e) => Err(e),
+                    None => Ok(score.unwrap_or_default()),
+                },
+                moves: serde_json::from_str(&moves).map_err(json_error)?,
+            },
+        ));
+    }
+
+    let matches = match_players
+        .into_iter()
+        .map(|(match_id, mut players)| {
+            players.sort_by_key(|(first, _)| !first);
+            let mut players = players.into_iter().map(|(_, p)| p);
+            match (players.next(), players.next(), players.next()) {
+                (Some(player1), Some(player2), None) => Ok(StoredMatch {
+                    moves: match_moves[&match_id] as u32,
+                    player1,
+                    player2,
+                }),
+                _ => Err(DbErr::Custom(format!("Match {match_id} is broken"))),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((
+        serde_json::to_string(&matches).map_err(json_error)?,
+        serde_json::to_string(&participants).map_err(json_error)?,
+    ))
+}
+
+/// Stores the rounds played since the conversion into the kept `round_results` as JSON blobs
+async fn restore_round_results(conn: &dyn ConnectionTrait) -> Result<(), DbErr> {
+    let backend = conn.get_database_backend();
+
+    let select = Query::select()
+        .column(round_result::Column::Id)
+        .from(round_result::Entity)
+        .to_owned();
+    let converted = conn
+        .query_all(backend.build(&select))
+        .await?
+        .iter()
+        .map(|r| r.try_get("", round_result::Column::Id.as_str()))
+        .collect::<Result<BTreeSet<i32>, _>>()?;
+
+    let select = Query::select()
+        .columns([round::Column::Id, round::Column::Datetime])
+        .from(round::Entity)
+        .order_by(round::Column::Id, Order::Asc)
+        .to_owned();
+    for row in conn.query_all(backend.build(&select)).await? {
+        let id: i32 = row.try_get("", round::Column::Id.as_str())?;
+        if converted.contains(&id) {
+            continue;
+        }
+        let datetime: DateTimeUtc = row.try_get("", round::Column::Datetime.as_str())?;
+
+        let select = Query::select()
+            .columns([
+                (player_result::Entity, player_result::Column::MatchId),
+                (player_result::Entity, player_result::Column::UserId),
+                (player_result::Entity, player_result::Column::SubmissionId),
+                (player_result::Entity, player_result::Column::First),
+                (player_result::Entity, player_result::Column::Score),
+                (player_result::Entity, player_result::Column::Error),
+                (player_result::Entity, player_result::Column::Moves),
+            ])
+            .expr_as(
+                Expr::tbl(match_result::Entity, match_result::Column::Moves).into_simple_expr(),
+                Alias::new("match_moves"),
+            )
+            .from(player_result::Entity)
+            .inner_join(
+                match_result::Entity,
+                Expr::tbl(match_result::Entity, match_result::Column::Id)
+                    .equals(player_result::Entity, player_result::Column::MatchId),
+            )
+            .and_where(Expr::tbl(player_result::Entity, player_result::Column::RoundId).eq(id))
+            .to_owned();
+        let (result, participants) = round_blobs(&conn.query_all(backend.build(&select)).await?)?;
+
+        let insert = Query::insert()
+            .into_table(round_result::Entity)
+            .columns([
+                round_result::Column::Id,
+                round_result::Column::Result,
+                round_result::Column::Participants,
+                round_result::Column::Datetime,
+            ])
+            .values_panic([
+                id.into(),
+                result.into(),
+                participants.into(),
+                datetime.into(),
+            ])
+            .to_owned();
+        conn.execute(backend.build(&insert)).await?;
+    }
+
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                sea_query::Table::create()
+                    .table(round::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(round::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(round::Column::Datetime)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                sea_query::Index::create()
+                    .name("IX_Round_Datetime")
+                    .table(round::Entity)
+                    .col(round::Column::Datetime)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                sea_query::Table::create()
+                    .table(match_result::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(match_result::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(match_result::Column::RoundId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(match_result::Column::Moves)
+                            .integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                sea_query::Index::create()
+                    .name("IX_Match_RoundId")
+                    .table(match_result::Entity)
+                    .col(match_result::Column::RoundId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                sea_query::Table::create()
+                    .table(player_result::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(player_result::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(player_result::Column::MatchId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(player_result::Column::RoundId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(player_result::Column::UserId)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(player_result::Column::SubmissionId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(player_result::Column::OpponentUserId)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(player_result::Column::OpponentSubmissionId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(player_result::Column::First)
+                            .boolean()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(player_result::Column::Score).double())
+                    .col(ColumnDef::new(player_result::Column::Error).text())
+                    .col(
+                        ColumnDef::new(player_result::Column::Moves)
+                            .text()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        for (name, columns) in [
+            (
+                "IX_PlayerResult_RoundId_UserId",
+                vec![
+                    player_result::Column::RoundId,
+                    player_result::Column::UserId,
+                ],
+            ),
+            (
+                "IX_PlayerResult_UserId_OpponentUserId",
+                vec![
+                    player_result::Column::UserId,
+                    player_result::Column::OpponentUserId,
+                ],
+            ),
+            (
+                "IX_PlayerResult_SubmissionId",
+                vec![player_result::Column::SubmissionId],
+            ),
+            (
+                "IX_PlayerResult_MatchId",
+                vec![player_result::Column::MatchId],
+            ),
+        ] {
+            let mut index = sea_query::Index::create();
+            index.name(name).table(player_result::Entity);
+            for c in columns {
+                index.col(c);
+            }
+            manager.create_index(index).await?;
+        }
+
+        // round_results is kept, so that the migration can be rolled back
+        convert_round_results(manager.get_connection()).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        restore_round_results(manager.get_connection()).await?;
+
+        for table in [
+            player_result::Entity.into_table_ref(),
+            match_result::Entity.into_table_ref(),
+            round::Entity.into_table_ref(),
+        ] {
+            manager
+                .drop_table(sea_query::Table::drop().table(table).to_owned())
+                .await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+use crate::drop_column;
+use entity::{submission, user};
+use sea_schema::migration::{
+    sea_query::{self, *},
+    *,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220330_000010_add_late_submissions"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                sea_query::Table::alter()
+                    .table(user::Entity)
+                    .add_column(ColumnDef::new(user::Column::Deadline).timestamp())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                sea_query::Table::alter()
+                    .table(submission::Entity)
+                    .add_column(ColumnDef::new(submission::Column::LatePenalty).double())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                sea_query::Table::alter()
+                    .table(submission::Entity)
+                    .add_column(
+                        ColumnDef::new(submission::Column::Ranked)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_column(manager, submission::Entity, submission::Column::Ranked).await?;
+        drop_column(manager, submission::Entity, submission::Column::LatePenalty).await?;
+        drop_column(manager, user::Entity, user::Column::Deadline).await
+    }
+}
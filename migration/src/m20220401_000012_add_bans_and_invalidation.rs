@@ -0,0 +1,51 @@
+use crate::drop_column;
+use entity::{submission, user};
+use sea_schema::migration::{
+    sea_query::{self, *},
+    *,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20220401_000012_add_bans_and_invalidation"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                sea_query::Table::alter()
+                    .table(user::Entity)
+                    .add_column(
+                        ColumnDef::new(user::Column::Banned)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                sea_query::Table::alter()
+                    .table(submission::Entity)
+                    .add_column(
+                        ColumnDef::new(submission::Column::Invalidated)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_column(manager, submission::Entity, submission::Column::Invalidated).await?;
+        drop_column(manager, user::Entity, user::Column::Banned).await
+    }
+}
@@ -0,0 +1,525 @@
+use crate::api::rounds::compute_final_standings;
+use crate::api::submissions::{
+    check_jdk, parse_submission_id, read_upload, validate_code, SubmissionDetails,
+    SubmissionSummary, SubmitResponse, ValidationReport,
+};
+use crate::behaviour::{compute_behaviour_report, BehaviourReport};
+use crate::contest::Phase;
+use crate::grading::{compute_grades, grades_csv};
+use crate::house::HOUSE_PREFIX;
+use crate::similarity::{compute_similarity_report, SimilarityReport};
+use crate::validation_queue::{JobStatus, QueuedJob};
+use crate::{OpenIdConnectRequestExt, State};
+use chrono::Utc;
+use entity::house_strategy;
+use entity::sea_orm::prelude::DateTimeUtc;
+use execution::jdk::JdkVersion;
+use execution::matchmaker::{single_file_sources, SourceFile};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tide::log::info;
+use tide::{Body, Request, StatusCode};
+use tracing::instrument;
+
+#[derive(Deserialize, Debug)]
+struct SimilarityQuery {
+    #[serde(default = "default_min_similarity")]
+    min_similarity: f64,
+    #[serde(default = "default_min_behaviour_similarity")]
+    min_behaviour_similarity: f64,
+    #[serde(default = "default_behaviour_rounds")]
+    behaviour_rounds: u64,
+}
+
+fn default_min_similarity() -> f64 {
+    0.5
+}
+
+fn default_min_behaviour_similarity() -> f64 {
+    0.95
+}
+
+fn default_behaviour_rounds() -> u64 {
+    20
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PlagiarismReport {
+    pub source: SimilarityReport,
+    pub behaviour: BehaviourReport,
+}
+
+#[instrument(skip(req))]
+pub async fn get_similarity_report(req: Request<State>) -> tide::Result<Body> {
+    let query: SimilarityQuery = req.query()?;
+
+    let submissions = req.state().db.get_all_submissions().await?;
+    let rounds = req
+        .state()
+        .db
+        .get_last_rounds_with_participants(query.behaviour_rounds)
+        .await?;
+
+    let report = async_std::task::spawn_blocking(move || PlagiarismReport {
+        source: compute_similarity_report(&submissions, query.min_similarity),
+        behaviour: compute_behaviour_report(&rounds, query.min_behaviour_similarity),
+    })
+    .await;
+
+    Body::from_json(&report)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeadlineOverride {
+    /// The deadline of the contest applies if not set
+    pub deadline: Option<DateTimeUtc>,
+}
+
+/// Sets or clears the deadline override of a user, e.g. for an approved extension
+#[instrument(skip(req))]
+pub async fn set_user_deadline(mut req: Request<State>) -> tide::Result {
+    let user_id = req.param("user_id")?.to_string();
+    let setting: DeadlineOverride = req.body_json().await?;
+
+    let db = &req.state().db;
+    let mut user = db.get_user(&user_id).await?;
+    user.deadline = setting.deadline;
+    db.update_user(user).await?;
+
+    info!("The deadline of {user_id} is set to {:?}", setting.deadline);
+
+    Ok(tide::Response::new(StatusCode::Ok))
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum GradesFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize, Debug)]
+struct GradesQuery {
+    #[serde(default)]
+    format: GradesFormat,
+}
+
+/// Grades of all the users by the rubric, from the live scoreboard until the final tournament is over
+#[instrument(skip(req))]
+pub async fn get_grades(req: Request<State>) -> tide::Result<Body> {
+    let query: GradesQuery = req.query()?;
+    let state = req.state();
+
+    let final_standings = state.db.count_final_rounds().await? >= state.contest.final_rounds;
+    let standings = if final_standings {
+        compute_final_standings(&state.db, &state.contest.scoring).await?
+    } else {
+        state.round_cache.lock_ref().scoreboard.clone()
+    };
+    let submissions = state.db.get_all_submissions().await?;
+
+    // the users are graded by the submission that played in the rounds the standings come from
+    let (rounds, _) = if final_standings {
+        state.db.get_final_rounds_results().await?
+    } else {
+        let window = &state.contest.scoring.window;
+        state.db.get_last_rounds_results(window).await?
+    };
+    let mut played = HashMap::new();
+    // newest first
+    for (participants, _) in rounds {
+        for (user_id, submission_id) in participants {
+            played.entry(user_id).or_insert(submission_id);
+        }
+    }
+
+    let grades = compute_grades(
+        &state.contest.rubric,
+        &standings,
+        final_standings,
+        &submissions,
+        &played,
+    );
+
+    match query.format {
+        GradesFormat::Json => Body::from_json(&grades),
+        GradesFormat::Csv => {
+            let mut body = Body::from_string(grades_csv(&grades));
+            body.set_mime("text/csv");
+            Ok(body)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HouseStrategyInfo {
+    pub id: i32,
+    /// Without the prefix it plays under
+    pub name: String,
+    pub jdk_version: i32,
+    pub hidden: bool,
+}
+
+/// The house strategies added by the admins, the configured dummy ones are not listed
+#[instrument(skip(req))]
+pub async fn get_house_strategies(req: Request<State>) -> tide::Result<Body> {
+    let strategies = req.state().db.get_house_strategies().await?;
+
+    Body::from_json(
+        &strategies
+            .into_iter()
+            .map(|s| HouseStrategyInfo {
+                id: s.id,
+                name: s.name,
+                jdk_version: s.jdk_version,
+                hidden: s.hidden,
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+#[derive(Deserialize, Debug)]
+struct AddHouseStrategyQuery {
+    name: String,
+    #[serde(default)]
+    hidden: bool,
+    jdk: Option<u32>,
+}
+
+/// Queues a house strategy for validation, it plays from the next round on once it is added.
+///
+/// The code is uploaded as for a submission
+#[instrument(skip(req))]
+pub async fn add_house_strategy(mut req: Request<State>) -> tide::Result {
+    let query: AddHouseStrategyQuery = req.query()?;
+    let jdk = check_jdk(&req.state().contest, query.jdk)?;
+
+    let taken = req.state().contest.house_strategies.contains(&query.name)
+        || req
+            .state()
+            .db
+            .get_house_strategies()
+            .await?
+            .iter()
+            .any(|s| s.name == query.name);
+    if query.name.is_empty() || taken {
+        return Err(tide::http::Error::from_str(
+            StatusCode::BadRequest,
+            format!("The name {:?} is empty or taken", query.name),
+        ));
+    }
+
+    let (code, files) = read_upload(&mut req).await?;
+
+    let job_id =
+        req.state()
+            .validation
+            .enqueue(QueuedJob::HouseStrategy(PendingHouseStrategy {
+                user_id: req.user_id().unwrap(),
+                name: query.name,
+                files: files.unwrap_or_else(|| single_file_sources(code)),
+                jdk,
+                hidden: query.hidden,
+            }))?;
+
+    Ok(tide::Response::builder(StatusCode::Accepted)
+        .body(Body::from_json(&SubmitResponse { job_id })?)
+        .build())
+}
+
+/// An uploaded house strategy waiting for validation
+#[derive(Debug)]
+pub struct PendingHouseStrategy {
+    /// The admin who uploaded it
+    pub user_id: String,
+    pub name: String,
+    pub files: Vec<SourceFile>,
+    pub jdk: JdkVersion,
+    pub hidden: bool,
+}
+
+/// Validates the house strategy and adds it if it is valid, returning its id
+#[instrument(skip(state, on_status))]
+pub async fn validate_and_add_house_strategy(
+    state: &State,
+    strategy: PendingHouseStrategy,
+    on_status: impl Fn(JobStatus),
+) -> anyhow::Result<(Option<i32>, ValidationReport)> {
+    let PendingHouseStrategy {
+        name,
+        files,
+        jdk,
+        hidden,
+        ..
+    } = strategy;
+
+    let report = validate_code(
+        state.execution.clone(),
+        format!("{HOUSE_PREFIX}{name}"),
+        files.clone(),
+        jdk,
+        &on_status,
+    )
+    .await?;
+    if !report.valid {
+        return Ok((None, report));
+    }
+
+    let id = state
+        .db
+        .add_house_strategy(house_strategy::Model {
+            id: 0,
+            name: name.clone(),
+            files: serde_json::to_string(&files)?,
+            jdk_version: jdk.0 as i32,
+            hidden,
+        })
+        .await?;
+
+    info!("House strategy {name} is added");
+
+    Ok((Some(id), report))
+}
+
+#[instrument(skip(req))]
+pub async fn delete_house_strategy(req: Request<State>) -> tide::Result {
+    let id: i32 = req.param("id")?.parse().map_err(|_| {
+        tide::http::Error::from_str(StatusCode::BadRequest, "Invalid house strategy id")
+    })?;
+
+    if req.state().db.delete_house_strategy(id).await? {
+        info!("House strategy {id} is deleted");
+        Ok(tide::Response::new(StatusCode::Ok))
+    } else {
+        Err(tide::http::Error::from_str(
+            StatusCode::NotFound,
+            format!("House strategy {id} not found"),
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AdminUser {
+    pub user_id: String,
+    pub submissions: usize,
+    pub last_submission: Option<DateTimeUtc>,
+    /// The submission chosen by the user, the latest valid one plays if not set
+    pub active_submission: Option<i32>,
+    pub withdrawn: bool,
+    pub banned: bool,
+    pub deadline: Option<DateTimeUtc>,
+}
+
+/// Everyone who submitted something or has settings, by user id
+#[instrument(skip(req))]
+pub async fn get_users(req: Request<State>) -> tide::Result<Body> {
+    let db = &req.state().db;
+    let submissions = db.get_all_submissions().await?;
+    let mut users: BTreeMap<_, _> = db
+        .get_all_users()
+        .await?
+        .into_iter()
+        .map(|u| (u.user_id.clone(), u))
+        .collect();
+    for s in &submissions {
+        if !users.contains_key(&s.user_id) {
+            users.insert(s.user_id.clone(), db.get_user(&s.user_id).await?);
+        }
+    }
+
+    let by_user = submissions.iter().into_group_map_by(|s| s.user_id.as_str());
+
+    Body::from_json(
+        &users
+            .into_values()
+            .map(|u| {
+                let own = by_user.get(u.user_id.as_str());
+                AdminUser {
+                    submissions: own.map_or(0, |s| s.len()),
+                    last_submission: own.and_then(|s| s.iter().map(|s| s.datetime).max()),
+                    active_submission: u.active_submission,
+                    withdrawn: u.withdrawn,
+                    banned: u.banned,
+                    deadline: u.deadline,
+                    user_id: u.user_id,
+                }
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BanSetting {
+    pub banned: bool,
+}
+
+/// Bans or unbans a user, a banned user cannot submit and leaves the rounds
+#[instrument(skip(req))]
+pub async fn set_user_banned(mut req: Request<State>) -> tide::Result {
+    let user_id = req.param("user_id")?.to_string();
+    let setting: BanSetting = req.body_json().await?;
+
+    let db = &req.state().db;
+    let mut user = db.get_user(&user_id).await?;
+    user.banned = setting.banned;
+    db.update_user(user).await?;
+
+    info!("{user_id} is banned: {}", setting.banned);
+
+    Ok(tide::Response::new(StatusCode::Ok))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AdminSubmission {
+    pub user_id: String,
+    #[serde(flatten)]
+    pub summary: SubmissionSummary,
+}
+
+#[derive(Deserialize, Debug)]
+struct SubmissionsQuery {
+    /// All the users if not set
+    user_id: Option<String>,
+}
+
+/// The submissions of everyone or of one user, newest first
+#[instrument(skip(req))]
+pub async fn get_submissions(req: Request<State>) -> tide::Result<Body> {
+    let query: SubmissionsQuery = req.query()?;
+    let db = &req.state().db;
+
+    let submissions = match &query.user_id {
+        Some(user_id) => db.get_user_submissions(user_id).await?,
+        None => {
+            let mut all = db.get_all_submissions().await?;
+            all.reverse();
+            all
+        }
+    };
+
+    Body::from_json(
+        &submissions
+            .iter()
+            .map(|s| AdminSubmission {
+                user_id: s.user_id.clone(),
+                summary: SubmissionSummary::from(s),
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AdminSubmissionDetails {
+    pub user_id: String,
+    #[serde(flatten)]
+    pub details: SubmissionDetails,
+}
+
+/// Any submission along with its code
+#[instrument(skip(req))]
+pub async fn get_submission(req: Request<State>) -> tide::Result<Body> {
+    let id = parse_submission_id(req.param("id")?)?;
+
+    match req.state().db.get_submission(id).await? {
+        Some(s) => Body::from_json(&AdminSubmissionDetails {
+            user_id: s.user_id.clone(),
+            details: SubmissionDetails::new(&s)?,
+        }),
+        None => Err(tide::http::Error::from_str(
+            StatusCode::NotFound,
+            format!("Submission {id} not found"),
+        )),
+    }
+}
+
+async fn set_invalidated(req: Request<State>, invalidated: bool) -> tide::Result {
+    let id = parse_submission_id(req.param("id")?)?;
+
+    if req
+        .state()
+        .db
+        .set_submission_invalidated(id, invalidated)
+        .await?
+    {
+        info!("Submission {id} is invalidated: {invalidated}");
+        Ok(tide::Response::new(StatusCode::Ok))
+    } else {
+        Err(tide::http::Error::from_str(
+            StatusCode::NotFound,
+            format!("Submission {id} not found"),
+        ))
+    }
+}
+
+/// Makes the submission count as not valid: it stops playing and is graded as invalid
+#[instrument(skip(req))]
+pub async fn invalidate_submission(req: Request<State>) -> tide::Result {
+    set_invalidated(req, true).await
+}
+
+/// Undoes the invalidation, the submission counts as its validation found it again
+#[instrument(skip(req))]
+pub async fn reinstate_submission(req: Request<State>) -> tide::Result {
+    set_invalidated(req, false).await
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RoundsStatus {
+    pub phase: Phase,
+    pub paused: bool,
+}
+
+#[instrument(skip(req))]
+pub async fn get_rounds_status(req: Request<State>) -> tide::Result<Body> {
+    let state = req.state();
+    let played = state.db.count_final_rounds().await?;
+
+    Body::from_json(&RoundsStatus {
+        phase: state
+            .contest
+            .schedule
+            .phase(Utc::now(), played, state.contest.final_rounds),
+        paused: state.rounds.is_paused(),
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PauseSetting {
+    pub paused: bool,
+}
+
+/// Pauses or resumes the rounds, until the next restart
+#[instrument(skip(req))]
+pub async fn set_rounds_paused(mut req: Request<State>) -> tide::Result {
+    let setting: PauseSetting = req.body_json().await?;
+    req.state().rounds.set_paused(setting.paused);
+
+    info!("The rounds are paused: {}", setting.paused);
+
+    Ok(tide::Response::new(StatusCode::Ok))
+}
+
+/// Plays a round now instead of waiting for the next one, the phase decides which
+#[instrument(skip(req))]
+pub async fn force_round(req: Request<State>) -> tide::Result {
+    let state = req.state();
+    let played = state.db.count_final_rounds().await?;
+    let phase = state
+        .contest
+        .schedule
+        .phase(Utc::now(), played, state.contest.final_rounds);
+
+    if matches!(phase, Phase::NotStarted | Phase::Closed) {
+        return Err(tide::http::Error::from_str(
+            StatusCode::Conflict,
+            format!("No rounds are played in the {phase:?} phase"),
+        ));
+    }
+
+    state.rounds.force();
+    info!("A round is forced");
+
+    Ok(tide::Response::new(StatusCode::Accepted))
+}
@@ -6,6 +6,8 @@ use tracing::{debug, instrument};
 #[derive(Serialize, Deserialize, Debug)]
 pub struct User {
     pub username: String,
+    /// Whether the user has the admin role
+    pub admin: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -19,7 +21,10 @@ pub async fn get_me(req: Request<State>) -> tide::Result<Body> {
 
     debug!("user_id is {user_id:?}");
 
-    let user = user_id.map(|f| User { username: f });
+    let user = user_id.map(|f| User {
+        username: f,
+        admin: req.is_admin(),
+    });
     let me = Me { user };
 
     debug!("returning {me:?}");
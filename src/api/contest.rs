@@ -0,0 +1,57 @@
+use crate::contest::{LatePolicy, Phase, Schedule};
+use crate::{OpenIdConnectRequestExt, State};
+use chrono::Utc;
+use entity::sea_orm::prelude::DateTimeUtc;
+use serde::{Deserialize, Serialize};
+use tide::{Body, Request, StatusCode};
+use tracing::instrument;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ContestStatus {
+    pub phase: Phase,
+    pub schedule: Schedule,
+    pub final_rounds: u64,
+    pub final_rounds_played: u64,
+    pub late_policy: LatePolicy,
+    /// The submission deadline of the caller, differs from the one in the schedule if extended
+    pub your_deadline: Option<DateTimeUtc>,
+}
+
+#[instrument(skip(req))]
+pub async fn get_status(req: Request<State>) -> tide::Result<Body> {
+    let contest = &req.state().contest;
+    let played = req.state().db.count_final_rounds().await?;
+    let user_deadline = match req.user_id() {
+        Some(user_id) => req.state().db.get_user(&user_id).await?.deadline,
+        None => None,
+    };
+
+    Body::from_json(&ContestStatus {
+        phase: contest
+            .schedule
+            .phase(Utc::now(), played, contest.final_rounds),
+        schedule: contest.schedule.clone(),
+        final_rounds: contest.final_rounds,
+        final_rounds_played: played,
+        late_policy: contest.late_policy.clone(),
+        your_deadline: user_deadline.or(contest.schedule.deadline),
+    })
+}
+
+/// The official standings, computed from the final tournament
+#[instrument(skip(req))]
+pub async fn get_standings(req: Request<State>) -> tide::Result<Body> {
+    let cache = req.state().round_cache.lock_ref().clone();
+
+    match cache.standings_json() {
+        Some(json) => {
+            let mut body = Body::from_string(json.to_string());
+            body.set_mime(tide::http::mime::JSON);
+            Ok(body)
+        }
+        None => Err(tide::http::Error::from_str(
+            StatusCode::NotFound,
+            "The final tournament is not over yet",
+        )),
+    }
+}
@@ -1,46 +1,98 @@
-use crate::{api, OpenIdConnectRequestExt, RoundResult, Scoreboard, State};
+use crate::round_cache::RoundCache;
+use crate::validation_queue::ValidationJob;
+use crate::{OpenIdConnectRequestExt, State};
 use futures_signals::signal::SignalExt;
 use futures_util::StreamExt;
-use std::ops::Deref;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 use tracing::instrument;
 
-#[instrument(skip(last_round, scoreboard))]
+/// Sends the scoreboard if it changed since the last time (it does not while frozen) and the matches
+#[instrument(skip(cache, sent_scoreboard))]
 async fn send_updates(
     user_id: &Option<String>,
-    last_round: &[RoundResult],
-    scoreboard: &Scoreboard,
+    admin: bool,
+    cache: &RoundCache,
+    sent_scoreboard: &mut String,
     sender: &tide::sse::Sender,
 ) -> anyhow::Result<()> {
-    sender
-        .send("scoreboard", serde_json::to_string(scoreboard)?, None)
-        .await?;
+    let scoreboard_json = cache.scoreboard_json(admin);
+    if sent_scoreboard != scoreboard_json {
+        sender.send("scoreboard", scoreboard_json, None).await?;
+        *sent_scoreboard = scoreboard_json.to_string();
+    }
 
     if let Some(user_id) = user_id {
-        let matches_json = serde_json::to_string(&api::rounds::compute_matches(
-            &last_round,
-            scoreboard,
-            user_id,
-        )?)?;
+        sender
+            .send("matches", cache.matches_json(user_id), None)
+            .await?;
+    }
+
+    Ok(())
+}
 
-        sender.send("matches", matches_json, None).await?;
+/// Sends the validation jobs of the user whose status changed since the last time
+#[instrument(skip(jobs, sent))]
+async fn send_job_updates(
+    user_id: &str,
+    jobs: &BTreeMap<u64, ValidationJob>,
+    sent: &mut HashMap<u64, String>,
+    sender: &tide::sse::Sender,
+) -> anyhow::Result<()> {
+    for job in jobs.values().filter(|j| j.user_id == user_id) {
+        let job_json = serde_json::to_string(job)?;
+        if sent.get(&job.id) != Some(&job_json) {
+            sender.send("validation", &job_json, None).await?;
+            sent.insert(job.id, job_json);
+        }
     }
 
     Ok(())
 }
 
+enum Update {
+    Round(Arc<RoundCache>),
+    Jobs(Arc<BTreeMap<u64, ValidationJob>>),
+}
+
 #[instrument(skip(req))]
 pub async fn process_events(
     req: tide::Request<State>,
     sender: tide::sse::Sender,
 ) -> tide::Result<()> {
-    let mutable = &req.state().scoreboard_signal;
+    let mutable = &req.state().round_cache;
     let user_id = req.user_id();
 
-    let mut stream = mutable.signal_cloned().to_stream();
+    let rounds = mutable.signal_cloned().to_stream().map(Update::Round);
+    let jobs = req
+        .state()
+        .validation
+        .signal()
+        .to_stream()
+        .map(Update::Jobs);
+    let mut stream = futures_util::stream::select(rounds, jobs);
+
+    let mut sent_jobs = HashMap::new();
+    let mut sent_scoreboard = String::new();
 
-    while let Some(arc) = stream.next().await {
-        let (last_round, scoreboard) = arc.deref();
-        send_updates(&user_id, last_round, scoreboard, &sender).await?;
+    while let Some(update) = stream.next().await {
+        match update {
+            Update::Round(cache) => {
+                send_updates(
+                    &user_id,
+                    req.is_admin(),
+                    &cache,
+                    &mut sent_scoreboard,
+                    &sender,
+                )
+                .await?;
+            }
+            Update::Jobs(jobs) => {
+                if let Some(user_id) = &user_id {
+                    send_job_updates(user_id, &jobs, &mut sent_jobs, &sender).await?;
+                }
+            }
+        }
     }
 
     Ok(())
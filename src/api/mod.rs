@@ -1,4 +1,7 @@
+pub mod admin;
 pub mod auth;
+pub mod contest;
 pub mod events;
 pub mod rounds;
+pub mod sparring;
 pub mod submissions;
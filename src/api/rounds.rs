@@ -1,23 +1,90 @@
+use crate::contest::{ErrorPolicy, ScoringConfig};
+use crate::head_to_head::compute_head_to_head;
+use crate::house::is_house;
+use crate::replay::replay_match;
 use crate::{Database, OpenIdConnectRequestExt, State};
-use average::{Estimate, Mean};
+use average::{Estimate, Mean, WeightedMeanWithError};
+use entity::scoreboard_entry;
 use entity::sea_orm::prelude::{DateTime, DateTimeUtc};
 use execution::matchmaker::{PlayerResult, RoundResult};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap};
-use tide::{Body, Request};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use tide::{Body, Request, StatusCode};
 use tracing::instrument;
 
+/// z-score of the 95% confidence interval
+const CONFIDENCE_Z: f64 = 1.96;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PositionStats {
+    /// Number of the matches counted
+    pub samples: u64,
+    /// `None` with fewer than 2 matches
+    pub std_dev: Option<f64>,
+    /// 95% confidence interval of the score, `None` with fewer than 2 matches
+    pub confidence_interval: Option<(f64, f64)>,
+    /// The score can not be told apart from the one of the position above
+    pub tied: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Scoreboard {
     pub datetime: DateTimeUtc,
-    pub positions: Vec<(String, f64)>,
+    /// (user id, score, stats), the stats are not stored along with the past scoreboards
+    pub positions: Vec<(String, f64, Option<PositionStats>)>,
+    /// How the failed matches were counted, not stored along with the past scoreboards
+    pub error_policy: Option<ErrorPolicy>,
+    /// Set if this is the scoreboard at the freeze time shown instead of the live one
+    pub frozen_at: Option<DateTimeUtc>,
+    /// Ranks gained since the freeze by user id, once the contest is over
+    pub rank_changes: Option<BTreeMap<String, i32>>,
+    /// Names of the house strategies among the positions, they are there for reference
+    #[serde(default)]
+    pub house: BTreeSet<String>,
+}
+
+impl Scoreboard {
+    fn new(
+        datetime: DateTimeUtc,
+        positions: Vec<(String, f64, Option<PositionStats>)>,
+        error_policy: Option<ErrorPolicy>,
+    ) -> Self {
+        Scoreboard {
+            datetime,
+            house: positions
+                .iter()
+                .map(|p| &p.0)
+                .filter(|name| is_house(name))
+                .cloned()
+                .collect(),
+            positions,
+            error_policy,
+            frozen_at: None,
+            rank_changes: None,
+        }
+    }
+
+    /// The scoreboard without the given players
+    pub fn without(&self, players: &BTreeSet<String>) -> Scoreboard {
+        let mut res = self.clone();
+        res.positions.retain(|p| !players.contains(&p.0));
+        res.house.retain(|name| !players.contains(name));
+        if let Some(rank_changes) = &mut res.rank_changes {
+            rank_changes.retain(|name, _| !players.contains(name));
+        }
+        res
+    }
 }
 
 impl Default for Scoreboard {
     fn default() -> Self {
         Scoreboard {
             positions: Default::default(),
+            error_policy: None,
+            frozen_at: None,
+            rank_changes: None,
+            house: BTreeSet::new(),
             datetime: DateTimeUtc::from_utc(DateTime::from_timestamp(0, 0), chrono::Utc),
         }
     }
@@ -29,88 +96,194 @@ pub struct RedactedMatchResult {
     pub opponent_result: Result<f64, String>,
     pub opponent_name: String,
     pub opponent_scoreboard_score: f64,
+    /// Mean of what the matches counted for you on the scoreboard, `None` if none of them counted
+    pub your_counted_score: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PlayerMatches {
     pub matches: Vec<RedactedMatchResult>,
     pub round_time: DateTimeUtc,
+    pub error_policy: ErrorPolicy,
 }
 
 fn round_score(score: f64) -> f64 {
     (score * 1000.0).round() / 1000.0
 }
 
+/// What the match counts for each of the players on the scoreboard, `None` if it is not counted for them
+pub fn counted_scores(
+    policy: &ErrorPolicy,
+    moves: u32,
+    player1: &PlayerResult,
+    player2: &PlayerResult,
+) -> (Option<f64>, Option<f64>) {
+    let failed = (player1.outcome.is_err(), player2.outcome.is_err());
+    if let (Ok(s1), Ok(s2)) = (&player1.outcome, &player2.outcome) {
+        return (Some(*s1), Some(*s2));
+    }
+
+    match policy {
+        ErrorPolicy::Forfeit { score } => (
+            Some(if failed.0 { 0.0 } else { *score }),
+            Some(if failed.1 { 0.0 } else { *score }),
+        ),
+        ErrorPolicy::Penalty { score } => (failed.0.then_some(*score), failed.1.then_some(*score)),
+        ErrorPolicy::Exclude => (None, None),
+        ErrorPolicy::Partial => {
+            let replay = replay_match(moves, player1, player2);
+            let last = replay.turns.last();
+            (
+                Some(last.map_or(0.0, |t| t.your_score)),
+                Some(last.map_or(0.0, |t| t.opponent_score)),
+            )
+        }
+    }
+}
+
 #[instrument]
-pub async fn compute_scoreboard(db: &Database) -> anyhow::Result<Scoreboard> {
-    let (rounds, time) = db.get_last_rounds_results().await?;
+pub async fn compute_scoreboard(
+    db: &Database,
+    scoring: &ScoringConfig,
+) -> anyhow::Result<Scoreboard> {
+    let (rounds, time) = db.get_last_rounds_results(&scoring.window).await?;
 
-    let mut mean_values = HashMap::<_, Mean>::new();
+    let active: Option<HashMap<_, _>> = if scoring.active_submission_only {
+        Some(
+            db.get_active_submissions()
+                .await?
+                .into_iter()
+                .map(|s| (s.user_id, s.id))
+                .collect(),
+        )
+    } else {
+        None
+    };
+
+    Ok(score_rounds(rounds, time, scoring, active.as_ref()))
+}
 
-    for res in rounds
+/// The official standings from the rounds of the final tournament, all of them weigh the same
+#[instrument]
+pub async fn compute_final_standings(
+    db: &Database,
+    scoring: &ScoringConfig,
+) -> anyhow::Result<Scoreboard> {
+    let (rounds, time) = db.get_final_rounds_results().await?;
+
+    let scoring = ScoringConfig {
+        half_life: None,
+        ..scoring.clone()
+    };
+
+    Ok(score_rounds(rounds, time, &scoring, None))
+}
+
+/// Computes the scoreboard from the rounds (newest first) played until `time`,
+/// counting only the matches of the `active` submissions if they are given
+fn score_rounds(
+    rounds: Vec<(BTreeMap<String, i32>, RoundResult)>,
+    time: DateTimeUtc,
+    scoring: &ScoringConfig,
+    active: Option<&HashMap<String, i32>>,
+) -> Scoreboard {
+    let mut mean_values = HashMap::<_, WeightedMeanWithError>::new();
+
+    // the rounds are newest first
+    for (age, (participants, round)) in rounds.into_iter().enumerate() {
+        let weight = scoring.weight(age);
+
+        // filter out the games of the earlier submissions if asked to
+        let counted = |name: &str| {
+            is_house(name)
+                || active.is_none_or(|active| {
+                    let submission = participants.get(name);
+                    submission.is_some() && active.get(name) == submission
+                })
+        };
+
+        for m in &round.0 {
+            let scores = counted_scores(&scoring.error_policy, m.moves, &m.player1, &m.player2);
+
+            for (player, score) in [(&m.player1, scores.0), (&m.player2, scores.1)] {
+                match score {
+                    Some(score) if counted(&player.player_name) => mean_values
+                        .entry(player.player_name.clone())
+                        .or_insert_with(WeightedMeanWithError::new)
+                        .add(score, weight),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let mut positions: Vec<_> = mean_values
         .into_iter()
-        .flat_map(|f| f.0)
-        // get all pairs of players (p1, p2) and (p2, p1) for each match
-        .flat_map(|f| {
-            [
-                (f.player1.clone(), f.player2.clone()),
-                (f.player2, f.player1),
-            ]
+        .map(|(name, mean)| {
+            // the variance of a single match is undefined
+            let spread = (mean.len() >= 2).then(|| {
+                let error = CONFIDENCE_Z * mean.error();
+                (
+                    round_score(mean.sample_variance().sqrt()),
+                    (
+                        round_score(mean.weighted_mean() - error),
+                        round_score(mean.weighted_mean() + error),
+                    ),
+                )
+            });
+            let stats = PositionStats {
+                samples: mean.len(),
+                std_dev: spread.map(|s| s.0),
+                confidence_interval: spread.map(|s| s.1),
+                tied: false,
+            };
+            (name, round_score(mean.weighted_mean()), Some(stats))
         })
-        // filter out all the games that have error on side of the opponent
-        .filter(|f| f.1.outcome.is_ok())
-        // don't need opponent any more
-        .map(|f| f.0)
-    {
-        mean_values
-            .entry(res.player_name)
-            .or_insert_with(Mean::new)
-            .add(res.outcome.unwrap_or(0.0));
-    }
+        // sort from top score to lower, then by name
+        .sorted_by(|(na, sa, _), (nb, sb, _)| sb.total_cmp(sa).then(na.cmp(nb)))
+        .collect();
 
-    let res = Scoreboard {
-        positions: mean_values
-            .into_iter()
-            .map(|(name, mean)| (name, round_score(mean.mean())))
-            // sort from top score to lower, then by name
-            .sorted_by(|(na, sa), (nb, sb)| sb.partial_cmp(sa).unwrap().then(na.cmp(nb)))
-            .collect(),
-        datetime: time,
-    };
+    if scoring.tie_indistinguishable {
+        for i in 1..positions.len() {
+            let above = positions[i - 1].2.as_ref().unwrap().confidence_interval;
+            let stats = positions[i].2.as_mut().unwrap();
+            stats.tied = match (stats.confidence_interval, above) {
+                (Some(own), Some(above)) => own.1 >= above.0,
+                _ => false,
+            };
+        }
+    }
 
-    Ok(res)
+    Scoreboard::new(time, positions, Some(scoring.error_policy.clone()))
 }
 
-pub fn compute_matches(
-    round: &[RoundResult],
+/// Aggregates the matches of a player, given as (moves, player result, opponent result), by opponent
+pub fn compute_player_matches(
+    matches: impl IntoIterator<Item = (u32, PlayerResult, PlayerResult)>,
     scoreboard: &Scoreboard,
-    player_name: &str,
+    error_policy: &ErrorPolicy,
 ) -> anyhow::Result<PlayerMatches> {
-    let mut matches_by_players: BTreeMap<_, Vec<(PlayerResult, PlayerResult)>> = BTreeMap::new();
-
-    for r in round
-        .iter()
-        .flat_map(|f| f.0.iter())
-        .flat_map(|f| {
-            [
-                (f.player1.clone(), f.player2.clone()),
-                (f.player2.clone(), f.player1.clone()),
-            ]
-        })
-        .filter(|f| f.0.player_name == player_name)
-    {
+    let mut matches_by_players: BTreeMap<_, Vec<(u32, PlayerResult, PlayerResult)>> =
+        BTreeMap::new();
+
+    for r in matches {
         matches_by_players
-            .entry(r.1.player_name.clone())
-            .or_insert_with(|| Vec::new())
+            .entry(r.2.player_name.clone())
+            .or_insert_with(Vec::new)
             .push(r);
     }
 
     let matches = matches_by_players
         .into_iter()
         .map(|(opponent_name, matches)| {
+            let counted: Mean = matches
+                .iter()
+                .filter_map(|(moves, us, them)| counted_scores(error_policy, *moves, us, them).0)
+                .collect();
+
             let (us, them) = matches.into_iter().fold(
                 (Ok(Mean::new()), Ok(Mean::new())),
-                |(us_mean, them_mean), (us, them)| {
+                |(us_mean, them_mean), (_, us, them)| {
                     let us_mean = us_mean.and_then(|mut m| {
                         m.add(us.outcome?);
                         Ok(m)
@@ -127,26 +300,23 @@ pub fn compute_matches(
             let us = us.map(|f| f.mean());
             let them = them.map(|f| f.mean());
 
-            let res = RedactedMatchResult {
+            RedactedMatchResult {
                 your_result: us,
                 opponent_result: them,
                 opponent_name: opponent_name.clone(),
+                // the opponents with no counted results are not on the scoreboard
                 opponent_scoreboard_score: scoreboard
                     .positions
                     .iter()
-                    .filter(|p| p.0 == opponent_name.as_str())
-                    .exactly_one()
-                    .unwrap()
-                    .1,
-            };
-
-            res
+                    .find(|p| p.0 == opponent_name.as_str())
+                    .map_or(0.0, |p| p.1),
+                your_counted_score: (!counted.is_empty()).then(|| round_score(counted.mean())),
+            }
         })
         .sorted_by(|a, b| {
             // sort from top score to lower, then by name
             b.opponent_scoreboard_score
-                .partial_cmp(&a.opponent_scoreboard_score)
-                .unwrap()
+                .total_cmp(&a.opponent_scoreboard_score)
                 .then(a.opponent_name.cmp(&b.opponent_name))
         })
         .collect();
@@ -154,23 +324,439 @@ pub fn compute_matches(
     Ok(PlayerMatches {
         matches,
         round_time: scoreboard.datetime,
+        error_policy: error_policy.clone(),
     })
 }
 
+/// Body of the already serialized JSON
+fn json_body(json: &str) -> Body {
+    let mut body = Body::from_string(json.to_string());
+    body.set_mime(tide::http::mime::JSON);
+    body
+}
+
+/// Ranks of the positions sorted from the top, the equal scores and the tied positions share the rank
+pub fn scoreboard_ranks(scoreboard: &Scoreboard) -> Vec<(String, i32, f64)> {
+    let mut res: Vec<(String, i32, f64)> = Vec::new();
+    for (i, (name, score, stats)) in scoreboard.positions.iter().enumerate() {
+        let tied = stats.as_ref().is_some_and(|s| s.tied);
+        let rank = match res.last() {
+            Some((_, rank, last_score)) if tied || last_score == score => *rank,
+            _ => i as i32 + 1,
+        };
+        res.push((name.clone(), rank, *score));
+    }
+    res
+}
+
+#[derive(Deserialize, Debug)]
+struct ScoreboardQuery {
+    /// Returns the scoreboard stored at that time instead of the current one
+    at: Option<DateTimeUtc>,
+}
+
+/// The scoreboard from the stored positions, top first
+pub fn stored_scoreboard(entries: Vec<scoreboard_entry::Model>) -> Scoreboard {
+    Scoreboard::new(
+        entries
+            .first()
+            .map(|e| e.datetime)
+            .unwrap_or_else(|| Scoreboard::default().datetime),
+        entries
+            .into_iter()
+            .map(|e| (e.user_id, e.score, None))
+            .collect(),
+        None,
+    )
+}
+
+/// Hides what happened after the freeze from everyone but the admins
+fn visible_until(req: &Request<State>) -> Option<DateTimeUtc> {
+    if req.is_admin() {
+        None
+    } else {
+        req.state().round_cache.lock_ref().frozen_at()
+    }
+}
+
 #[instrument(skip(req))]
 pub async fn get_scoreboard(req: Request<State>) -> tide::Result<Body> {
-    let res = compute_scoreboard(&req.state().db).await?;
+    let query: ScoreboardQuery = req.query()?;
 
-    Body::from_json(&res)
+    let at = match (query.at, visible_until(&req)) {
+        (None, _) => {
+            let cache = req.state().round_cache.lock_ref().clone();
+            return Ok(json_body(cache.scoreboard_json(req.is_admin())));
+        }
+        (Some(at), Some(frozen_at)) => at.min(frozen_at),
+        (Some(at), None) => at,
+    };
+
+    let entries = req.state().db.get_scoreboard_at(at).await?;
+    let scoreboard = stored_scoreboard(entries);
+
+    if req.is_admin() {
+        Body::from_json(&scoreboard)
+    } else {
+        let cache = req.state().round_cache.lock_ref().clone();
+        Body::from_json(&scoreboard.without(cache.hidden()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ScoreboardHistoryPoint {
+    pub round_id: i32,
+    pub datetime: DateTimeUtc,
+    pub rank: i32,
+    pub score: f64,
+}
+
+#[derive(Deserialize, Debug)]
+struct ScoreboardHistoryQuery {
+    /// The caller if not set
+    player: Option<String>,
+}
+
+#[instrument(skip(req))]
+pub async fn get_scoreboard_history(req: Request<State>) -> tide::Result<Body> {
+    let query: ScoreboardHistoryQuery = req.query()?;
+    let player = query
+        .player
+        .or_else(|| req.user_id())
+        .ok_or_else(|| tide::http::Error::from_str(StatusCode::BadRequest, "No player is given"))?;
+
+    if !req.is_admin()
+        && req
+            .state()
+            .round_cache
+            .lock_ref()
+            .hidden()
+            .contains(&player)
+    {
+        return Err(tide::http::Error::from_str(
+            StatusCode::NotFound,
+            format!("Player {player} not found"),
+        ));
+    }
+
+    let history = req.state().db.get_scoreboard_history(&player).await?;
+    let until = visible_until(&req);
+
+    Body::from_json(
+        &history
+            .into_iter()
+            .filter(|e| until.is_none_or(|until| e.datetime <= until))
+            .map(|e| ScoreboardHistoryPoint {
+                round_id: e.round_id,
+                datetime: e.datetime,
+                rank: e.rank,
+                score: e.score,
+            })
+            .collect::<Vec<_>>(),
+    )
 }
 
 #[instrument(skip(req))]
 pub async fn get_matches(req: Request<State>) -> tide::Result<Body> {
-    let (rounds, _) = req.state().db.get_last_rounds_results().await?;
+    let user_id = req.user_id().unwrap();
+
+    Ok(json_body(
+        req.state().round_cache.lock_ref().matches_json(&user_id),
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+struct RoundsQuery {
+    #[serde(default = "default_rounds_limit")]
+    limit: u64,
+}
+
+fn default_rounds_limit() -> u64 {
+    20
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RoundInfo {
+    pub id: i32,
+    pub datetime: DateTimeUtc,
+    pub final_tournament: bool,
+    /// Whether the caller took part in the round
+    pub participated: bool,
+}
+
+#[instrument(skip(req))]
+pub async fn get_rounds(req: Request<State>) -> tide::Result<Body> {
+    let query: RoundsQuery = req.query()?;
+    let user_id = req.user_id();
+
+    let rounds = req
+        .state()
+        .db
+        .get_last_rounds_list(query.limit.min(1000))
+        .await?;
+
+    Body::from_json(
+        &rounds
+            .into_iter()
+            .map(|(round, participants)| RoundInfo {
+                id: round.id,
+                datetime: round.datetime,
+                final_tournament: round.final_tournament,
+                participated: user_id
+                    .as_ref()
+                    .is_some_and(|u| participants.contains_key(u)),
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Whether the player is a hidden house strategy the caller may not see
+fn is_hidden(req: &Request<State>, player: &str) -> bool {
+    !req.is_admin() && req.state().round_cache.lock_ref().hidden().contains(player)
+}
+
+#[derive(Deserialize, Debug)]
+struct ReplayQuery {
+    opponent: String,
+}
+
+/// Replays the match of the caller against the opponent in the round
+#[instrument(skip(req))]
+pub async fn get_match_replay(req: Request<State>) -> tide::Result<Body> {
+    let user_id = req.user_id().unwrap();
+    let query: ReplayQuery = req.query()?;
+    let round_id = req.param("id")?;
+
+    let not_found = || {
+        tide::http::Error::from_str(
+            StatusCode::NotFound,
+            format!("No match against {} in round {round_id}", query.opponent),
+        )
+    };
+    if is_hidden(&req, &query.opponent) {
+        return Err(not_found());
+    }
+
+    let (moves, you, opponent) = match round_id.parse() {
+        Ok(id) => {
+            req.state()
+                .db
+                .get_match(id, &user_id, &query.opponent)
+                .await?
+        }
+        Err(_) => None,
+    }
+    .ok_or_else(not_found)?;
+
+    let replay = replay_match(moves, &you, &opponent);
+
+    Body::from_json(&replay)
+}
+
+#[derive(Deserialize, Debug)]
+struct HeadToHeadQuery {
+    /// The caller if not set, only admins can look at the others
+    player: Option<String>,
+    opponent: String,
+}
+
+#[instrument(skip(req))]
+pub async fn get_head_to_head(req: Request<State>) -> tide::Result<Body> {
+    let query: HeadToHeadQuery = req.query()?;
+    let user_id = req.user_id().unwrap();
+
+    let player = query.player.unwrap_or_else(|| user_id.clone());
+    if player != user_id && query.opponent != user_id && !req.is_admin() {
+        return Err(tide::http::Error::from_str(
+            StatusCode::Forbidden,
+            "You can only look at your own matches",
+        ));
+    }
+    if let Some(hidden) = [&player, &query.opponent]
+        .into_iter()
+        .find(|p| is_hidden(&req, p))
+    {
+        return Err(tide::http::Error::from_str(
+            StatusCode::NotFound,
+            format!("No player {hidden}"),
+        ));
+    }
 
-    let scoreboard = compute_scoreboard(&req.state().db).await?;
+    let results = req
+        .state()
+        .db
+        .get_head_to_head_results(&player, &query.opponent)
+        .await?;
 
-    let res = compute_matches(&rounds, &scoreboard, &req.user_id().unwrap())?;
+    let res = async_std::task::spawn_blocking(move || {
+        compute_head_to_head(&results, &player, &query.opponent)
+    })
+    .await;
 
     Body::from_json(&res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use execution::matchmaker::MatchResult;
+
+    fn player(name: &str, outcome: Result<f64, String>, moves: &[i32]) -> PlayerResult {
+        PlayerResult {
+            player_name: name.to_string(),
+            outcome,
+            moves: moves.to_vec(),
+        }
+    }
+
+    /// The payoff of taking a field of value `x`, as in the fixture
+    fn payoff(x: f64) -> f64 {
+        10.0 * x.exp() / (1.0 + x.exp()) - 5.0
+    }
+
+    #[test]
+    fn finished_matches_count_as_played() {
+        let first = player("first", Ok(10.5), &[1, 2]);
+        let second = player("second", Ok(7.25), &[2, 1]);
+
+        for policy in [
+            ErrorPolicy::Forfeit { score: 3.0 },
+            ErrorPolicy::Penalty { score: -1.0 },
+            ErrorPolicy::Exclude,
+            ErrorPolicy::Partial,
+        ] {
+            assert_eq!(
+                counted_scores(&policy, 2, &first, &second),
+                (Some(10.5), Some(7.25))
+            );
+        }
+    }
+
+    #[test]
+    fn forfeit() {
+        let failed = player("failed", Err("Timeout".to_string()), &[1]);
+        let opponent = player("opponent", Ok(4.0), &[2]);
+        let policy = ErrorPolicy::Forfeit { score: 3.0 };
+
+        assert_eq!(
+            counted_scores(&policy, 1, &failed, &opponent),
+            (Some(0.0), Some(3.0))
+        );
+        assert_eq!(
+            counted_scores(&policy, 1, &opponent, &failed),
+            (Some(3.0), Some(0.0))
+        );
+    }
+
+    #[test]
+    fn penalty() {
+        let failed = player("failed", Err("Timeout".to_string()), &[1]);
+        let opponent = player("opponent", Ok(4.0), &[2]);
+        let policy = ErrorPolicy::Penalty { score: -1.0 };
+
+        assert_eq!(
+            counted_scores(&policy, 1, &failed, &opponent),
+            (Some(-1.0), None)
+        );
+        assert_eq!(
+            counted_scores(&policy, 1, &opponent, &failed),
+            (None, Some(-1.0))
+        );
+    }
+
+    #[test]
+    fn both_failed() {
+        let first = player("first", Err("Timeout".to_string()), &[]);
+        let second = player("second", Err("Invalid move".to_string()), &[]);
+
+        assert_eq!(
+            counted_scores(&ErrorPolicy::Forfeit { score: 3.0 }, 0, &first, &second),
+            (Some(0.0), Some(0.0))
+        );
+        assert_eq!(
+            counted_scores(&ErrorPolicy::Penalty { score: -1.0 }, 0, &first, &second),
+            (Some(-1.0), Some(-1.0))
+        );
+    }
+
+    #[test]
+    fn exclude() {
+        let failed = player("failed", Err("Timeout".to_string()), &[1]);
+        let opponent = player("opponent", Ok(4.0), &[2]);
+
+        assert_eq!(
+            counted_scores(&ErrorPolicy::Exclude, 1, &failed, &opponent),
+            (None, None)
+        );
+    }
+
+    #[test]
+    fn partial_replays_the_completed_turns() {
+        // the third move is the one that failed, it is not played
+        let failed = player("failed", Err("Invalid move".to_string()), &[1, 3, 7]);
+        let opponent = player("opponent", Ok(0.0), &[2, 1, 2]);
+
+        let (failed_score, opponent_score) =
+            counted_scores(&ErrorPolicy::Partial, 2, &failed, &opponent);
+        // both take a field of 1 in the first turn, then the failed player takes xC = 2 and the opponent xA = 0
+        let (failed_score, opponent_score) = (failed_score.unwrap(), opponent_score.unwrap());
+        assert!((failed_score - (payoff(1.0) + payoff(2.0))).abs() < 1e-9);
+        assert!((opponent_score - payoff(1.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn no_interval_for_a_single_match() {
+        let round = |score1: f64, score2: f64| {
+            let participants = [("first".to_string(), 1), ("second".to_string(), 2)].into();
+            let m = MatchResult {
+                moves: 0,
+                player1: player("first", Ok(score1), &[]),
+                player2: player("second", Ok(score2), &[]),
+            };
+            (participants, RoundResult(vec![m]))
+        };
+        let scoring = ScoringConfig {
+            tie_indistinguishable: true,
+            ..ScoringConfig::default()
+        };
+
+        let scoreboard = score_rounds(vec![round(3.0, 1.0)], Utc::now(), &scoring, None);
+        let stats: Vec<_> = scoreboard
+            .positions
+            .iter()
+            .map(|(name, _, stats)| (name.as_str(), stats.as_ref().unwrap()))
+            .collect();
+        assert_eq!(stats[0].0, "first");
+        for (_, stats) in stats {
+            assert_eq!(stats.samples, 1);
+            assert_eq!(stats.std_dev, None);
+            assert_eq!(stats.confidence_interval, None);
+            assert!(!stats.tied);
+        }
+
+        let scoreboard = score_rounds(
+            vec![round(3.0, 1.0), round(1.0, 3.0)],
+            Utc::now(),
+            &scoring,
+            None,
+        );
+        let (_, score, stats) = &scoreboard.positions[0];
+        let stats = stats.as_ref().unwrap();
+        assert_eq!(*score, 2.0);
+        assert_eq!(stats.std_dev, Some(1.414));
+        assert!(stats.confidence_interval.is_some());
+        assert!(scoreboard.positions[1].2.as_ref().unwrap().tied);
+    }
+
+    #[test]
+    fn partial_without_turns() {
+        let failed = player("failed", Err("Compilation error".to_string()), &[]);
+        let opponent = player("opponent", Ok(0.0), &[]);
+
+        assert_eq!(
+            counted_scores(&ErrorPolicy::Partial, 0, &failed, &opponent),
+            (Some(0.0), Some(0.0))
+        );
+    }
+}
@@ -0,0 +1,367 @@
+use crate::api::submissions::{
+    check_jdk, check_not_banned, get_own_submission, own_diagnostics, read_upload,
+    SubmissionSummary, SubmitResponse,
+};
+use crate::database::submission_sources;
+use crate::validation_queue::{JobStatus, QueuedJob};
+use crate::{OpenIdConnectRequestExt, State};
+use execution::diagnostics::Diagnostic;
+use execution::error::Error;
+use execution::jdk::JdkVersion;
+use execution::matchmaker::{
+    compile_matched_program, make_match_program, run_compiled_match, single_file_sources,
+    PlayerResult, SourceFile, DUMMY_STRATS,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use tide::{Body, Request, StatusCode};
+use tracing::{error, info, instrument};
+
+const MAX_OPPONENTS: usize = 8;
+/// Name of the caller's code in the sparring program
+const YOU: &str = "you";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Opponent {
+    Dummy { name: String },
+    Own { submission_id: i32 },
+    Partner { user_id: String },
+}
+
+impl FromStr for Opponent {
+    type Err = String;
+
+    /// Parses `dummy:<name>`, `own:<submission id>` or `partner:<user id>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("dummy", name)) => Ok(Opponent::Dummy {
+                name: name.to_string(),
+            }),
+            Some(("own", id)) => Ok(Opponent::Own {
+                submission_id: id
+                    .parse()
+                    .map_err(|_| format!("Invalid submission id {id}"))?,
+            }),
+            Some(("partner", user_id)) => Ok(Opponent::Partner {
+                user_id: user_id.to_string(),
+            }),
+            _ => Err(format!("Invalid opponent {s}")),
+        }
+    }
+}
+
+fn bad_request(message: impl Into<String>) -> tide::Error {
+    tide::http::Error::from_str(StatusCode::BadRequest, message.into())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SparringOpponents {
+    pub dummies: Vec<String>,
+    /// Valid submissions of the caller
+    pub own: Vec<SubmissionSummary>,
+    /// Users who opted in as sparring partners and have an active submission
+    pub partners: Vec<String>,
+}
+
+#[instrument(skip(req))]
+pub async fn get_opponents(req: Request<State>) -> tide::Result<Body> {
+    let user_id = req.user_id().unwrap();
+    let db = &req.state().db;
+
+    let own = db
+        .get_user_submissions(&user_id)
+        .await?
+        .iter()
+        .filter(|s| s.is_valid())
+        .map(SubmissionSummary::from)
+        .collect();
+
+    let active: Vec<_> = db
+        .get_active_submissions()
+        .await?
+        .into_iter()
+        .map(|s| s.user_id)
+        .collect();
+    let partners = db
+        .get_sparring_partners()
+        .await?
+        .into_iter()
+        .filter(|u| active.contains(u))
+        .collect();
+
+    Body::from_json(&SparringOpponents {
+        dummies: DUMMY_STRATS.iter().map(|(n, _)| n.to_string()).collect(),
+        own,
+        partners,
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SparringPartnerSetting {
+    pub enabled: bool,
+}
+
+/// Opts the active submission of the caller in or out of being an opponent in the others' sparring
+#[instrument(skip(req))]
+pub async fn set_sparring_partner(mut req: Request<State>) -> tide::Result {
+    let user_id = req.user_id().unwrap();
+    let setting: SparringPartnerSetting = req.body_json().await?;
+
+    let db = &req.state().db;
+    let mut user = db.get_user(&user_id).await?;
+    user.sparring_partner = setting.enabled;
+    db.update_user(user).await?;
+
+    Ok(tide::Response::new(StatusCode::Ok))
+}
+
+/// Finds the code of the opponent, checking that the caller can play against it
+async fn opponent_sources(
+    req: &Request<State>,
+    opponent: &Opponent,
+) -> tide::Result<(Vec<SourceFile>, Option<JdkVersion>)> {
+    let db = &req.state().db;
+
+    match opponent {
+        Opponent::Dummy { name } => {
+            let (_, code) = DUMMY_STRATS
+                .iter()
+                .find(|(n, _)| n == name)
+                .ok_or_else(|| bad_request(format!("Unknown dummy strategy {name}")))?;
+            Ok((single_file_sources(code.to_string()), None))
+        }
+        Opponent::Own { submission_id } => {
+            let submission = get_own_submission(req, *submission_id).await?;
+            if !submission.is_valid() {
+                return Err(bad_request(format!(
+                    "Submission {submission_id} did not pass the validation"
+                )));
+            }
+            Ok((
+                submission_sources(&submission)?,
+                Some(JdkVersion(submission.jdk_version as u32)),
+            ))
+        }
+        Opponent::Partner { user_id } => {
+            if !db.get_sparring_partners().await?.contains(user_id) {
+                return Err(bad_request(format!(
+                    "{user_id} is not a public sparring partner"
+                )));
+            }
+            let submission = db
+                .get_active_submissions()
+                .await?
+                .into_iter()
+                .find(|s| &s.user_id == user_id)
+                .ok_or_else(|| bad_request(format!("{user_id} has no active submission")))?;
+            Ok((
+                submission_sources(&submission)?,
+                Some(JdkVersion(submission.jdk_version as u32)),
+            ))
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct SparringQuery {
+    /// Comma-separated list of the opponents
+    opponents: String,
+    jdk: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SparringMatch {
+    pub opponent: Opponent,
+    pub moves: u32,
+    pub you: PlayerResult,
+    pub them: PlayerResult,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SparringReport {
+    /// Why the matches could not be played, if they could not
+    pub error: Option<String>,
+    /// javac errors and warnings in the uploaded files
+    pub diagnostics: Vec<Diagnostic>,
+    pub matches: Vec<SparringMatch>,
+}
+
+impl SparringReport {
+    fn failed(error: String, diagnostics: Vec<Diagnostic>) -> Self {
+        SparringReport {
+            error: Some(error),
+            diagnostics,
+            matches: Vec::new(),
+        }
+    }
+}
+
+/// The uploaded code and the opponents waiting to be played
+#[derive(Debug)]
+pub struct PendingSparring {
+    pub user_id: String,
+    pub opponents: Vec<Opponent>,
+    /// The sources and the JDK of the caller and of each opponent, by their names in the program
+    pub players: HashMap<String, (Vec<SourceFile>, JdkVersion)>,
+}
+
+/// Queues the uploaded code to be played against the chosen opponents, nothing is stored.
+///
+/// The report is available at /api/jobs/:id once the matches are over
+#[instrument(skip(req))]
+pub async fn spar(mut req: Request<State>) -> tide::Result {
+    let user_id = req.user_id().unwrap();
+    check_not_banned(req.state(), &user_id).await?;
+    let query: SparringQuery = req.query()?;
+
+    let mut opponents = Vec::new();
+    for o in query.opponents.split(',').filter(|o| !o.is_empty()) {
+        let o = Opponent::from_str(o).map_err(bad_request)?;
+        if !opponents.contains(&o) {
+            opponents.push(o);
+        }
+    }
+    if opponents.is_empty() || opponents.len() > MAX_OPPONENTS {
+        return Err(bad_request(format!(
+            "Choose from 1 to {MAX_OPPONENTS} opponents"
+        )));
+    }
+
+    let your_jdk = check_jdk(&req.state().contest, query.jdk)?;
+    let mut players = HashMap::new();
+    for (i, opponent) in opponents.iter().enumerate() {
+        let (files, opponent_jdk) = opponent_sources(&req, opponent).await?;
+        // the dummies are compiled like the caller's code
+        players.insert(
+            format!("opponent_{i}"),
+            (files, opponent_jdk.unwrap_or(your_jdk)),
+        );
+    }
+
+    let (code, files) = read_upload(&mut req).await?;
+    players.insert(
+        YOU.to_string(),
+        (files.unwrap_or_else(|| single_file_sources(code)), your_jdk),
+    );
+
+    info!("{user_id} spars against {opponents:?}");
+
+    let job_id = req
+        .state()
+        .validation
+        .enqueue(QueuedJob::Sparring(PendingSparring {
+            user_id,
+            opponents,
+            players,
+        }))?;
+
+    Ok(tide::Response::builder(StatusCode::Accepted)
+        .body(Body::from_json(&SubmitResponse { job_id })?)
+        .build())
+}
+
+/// Plays the matches of the queued sparring
+#[instrument(skip(state, on_status))]
+pub async fn play_sparring(
+    state: &State,
+    sparring: PendingSparring,
+    on_status: impl Fn(JobStatus),
+) -> anyhow::Result<SparringReport> {
+    let PendingSparring {
+        opponents, players, ..
+    } = sparring;
+    let execution = &state.execution;
+
+    // each one is compiled for their own JDK, by the newest one that runs all of them
+    let jdk = players.values().map(|(_, jdk)| *jdk).max().unwrap();
+
+    let matched = match make_match_program(&players, jdk) {
+        Ok(m) => m,
+        Err(e) => match e.downcast::<Error>() {
+            Ok(Error::InvalidSource(message)) => {
+                return Ok(SparringReport::failed(message, Vec::new()))
+            }
+            Ok(e) => return Err(e.into()),
+            Err(e) => return Err(e),
+        },
+    };
+
+    on_status(JobStatus::Compiling);
+    let compiled = match compile_matched_program(execution, &matched).await {
+        Ok(c) => c,
+        Err(e) => match e.downcast::<Error>() {
+            Ok(Error::CompilationError(_, diagnostics)) => {
+                let diagnostics = own_diagnostics(YOU, &diagnostics);
+                // the code of the opponents is not shown
+                let error = if diagnostics.is_empty() {
+                    "Compilation of the opponents failed".to_string()
+                } else {
+                    "Compilation failed".to_string()
+                };
+                return Ok(SparringReport::failed(error, diagnostics));
+            }
+            Ok(e) => return Err(e.into()),
+            Err(e) => return Err(e),
+        },
+    };
+    let diagnostics = own_diagnostics(YOU, compiled.diagnostics());
+
+    on_status(JobStatus::Running);
+    let round = match run_compiled_match(execution, &matched, &compiled).await {
+        Ok(r) => r,
+        Err(e) => match e.downcast::<Error>() {
+            Ok(Error::FixtureFailure(_, out, err, r)) => {
+                // the output may come from the code of the partners, it is only logged
+                error!(
+                    "Sparring fixture failed\n\
+                    STDOUT:\n{out}\n\n\
+                    STDERR:\n{err}\n\n\
+                    Additional error:\n{r:?}"
+                );
+                return Ok(SparringReport::failed(
+                    "Testing fixture failed".to_string(),
+                    diagnostics,
+                ));
+            }
+            Ok(e) => return Err(e.into()),
+            Err(e) => return Err(e),
+        },
+    };
+
+    let mut matches: Vec<_> = round
+        .0
+        .into_iter()
+        .filter_map(|m| {
+            let (you, mut them) = if m.player1.player_name == YOU {
+                (m.player1, m.player2)
+            } else if m.player2.player_name == YOU {
+                (m.player2, m.player1)
+            } else {
+                return None;
+            };
+            let index: usize = them.player_name.strip_prefix("opponent_")?.parse().ok()?;
+            let opponent = opponents.get(index)?.clone();
+
+            // only the errors of the caller's own code and of the dummies are shown
+            if let (Opponent::Partner { .. }, Err(e)) = (&opponent, &mut them.outcome) {
+                *e = "The opponent failed".to_string();
+            }
+
+            Some(SparringMatch {
+                opponent,
+                moves: m.moves,
+                you,
+                them,
+            })
+        })
+        .collect();
+    matches.sort_by_key(|m| opponents.iter().position(|o| o == &m.opponent));
+
+    Ok(SparringReport {
+        error: None,
+        diagnostics,
+        matches,
+    })
+}
@@ -1,7 +1,20 @@
+use crate::archive::{join_sources, unpack_sources};
+use crate::contest::{Acceptance, ContestConfig};
+use crate::database::submission_sources;
+use crate::validation_queue::{JobStatus, QueuedJob};
 use crate::{ExecutionState, OpenIdConnectRequestExt, State};
+use chrono::Utc;
 use entity::sea_orm::prelude::DateTimeUtc;
-use entity::submission;
-use execution::matchmaker::{match_with_dummy_strats, run_matched_program, PlayerResult};
+use entity::{submission, user};
+use execution::diagnostics::Diagnostic;
+use execution::jdk::JdkVersion;
+use execution::matchmaker::{
+    compile_matched_program, match_with_dummy_strats, run_compiled_match, single_file_sources,
+    PlayerResult, SourceFile,
+};
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use std::collections::BTreeMap;
 use std::fmt::Write;
 use std::sync::Arc;
 use std::time::SystemTime;
@@ -11,42 +24,101 @@ use tracing::instrument;
 
 const UPLOAD_LIMIT: usize = 1024 * 1024;
 
-#[instrument]
-async fn validate_code(
+/// Outcome of validating a submission against the dummy strategies
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub message: String,
+    /// Results of the validation matches, as (player, opponent) pairs
+    pub matches: Option<Vec<(PlayerResult, PlayerResult)>>,
+    /// javac errors and warnings in the submitted files
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ValidationReport {
+    fn failed(message: String, diagnostics: Vec<Diagnostic>) -> Self {
+        ValidationReport {
+            valid: false,
+            message,
+            matches: None,
+            diagnostics,
+        }
+    }
+}
+
+/// Keeps the diagnostics about the files of the user
+pub(crate) fn own_diagnostics(user_id: &str, diagnostics: &[Diagnostic]) -> Vec<Diagnostic> {
+    diagnostics
+        .iter()
+        .filter(|d| d.owner.as_deref() == Some(user_id))
+        .cloned()
+        .collect()
+}
+
+#[instrument(skip(on_status))]
+pub(crate) async fn validate_code(
     execution: Arc<ExecutionState>,
     user_id: String,
-    code: String,
-) -> anyhow::Result<(bool, String, Option<Vec<(PlayerResult, PlayerResult)>>)> {
-    let matched = match_with_dummy_strats(user_id.clone(), code)?;
+    files: Vec<SourceFile>,
+    jdk: JdkVersion,
+    on_status: &impl Fn(JobStatus),
+) -> anyhow::Result<ValidationReport> {
+    let matched = match match_with_dummy_strats(user_id.clone(), files, jdk) {
+        Ok(m) => m,
+        Err(err) => {
+            return match err.downcast::<execution::error::Error>() {
+                Ok(execution::error::Error::InvalidSource(message)) => {
+                    Ok(ValidationReport::failed(message, Vec::new()))
+                }
+                Ok(e) => Err(e.into()),
+                Err(e) => Err(e),
+            }
+        }
+    };
+
+    on_status(JobStatus::Compiling);
+    let compiled = match compile_matched_program(&execution, &matched).await {
+        Ok(c) => c,
+        Err(err) => {
+            return match err.downcast::<execution::error::Error>() {
+                Ok(execution::error::Error::CompilationError(output, diagnostics)) => {
+                    let diagnostics = own_diagnostics(&user_id, &diagnostics);
+
+                    let mut res = "Compilation failed:\n\n".to_string();
+                    if diagnostics.is_empty() {
+                        // nothing to blame the user's files for, show everything
+                        res.push_str(&output);
+                    }
+                    for d in diagnostics.iter() {
+                        writeln!(&mut res, "{d}\n").unwrap();
+                    }
 
-    let res = run_matched_program(execution, &matched).await;
+                    Ok(ValidationReport::failed(res, diagnostics))
+                }
+                Ok(e) => Err(e.into()),
+                Err(e) => Err(e),
+            };
+        }
+    };
+    let diagnostics = own_diagnostics(&user_id, compiled.diagnostics());
 
-    let res = match res {
+    on_status(JobStatus::Running);
+    let res = match run_compiled_match(&execution, &matched, &compiled).await {
         Ok(res) => res,
         Err(err) => match err.downcast::<execution::error::Error>() {
             Ok(e) => {
                 return match e {
-                    execution::error::Error::CompilationError(compiler_message) => {
-                        let mut res = "Compilation failed:\n".to_string();
-
-                        if compiler_message.contains("should be declared in a file") {
-                            res.push_str("NOTE: your strategy class should be called Strat?")
-                        }
-
-                        res.push_str(&format!("\n\n{compiler_message}"));
-
-                        Ok((false, res, None))
-                    }
-                    execution::error::Error::FixtureFailure(_, out, err, r) => Ok((
-                        false,
-                        format!(
-                            "Testing fixture failed\n\
+                    execution::error::Error::FixtureFailure(_, out, err, r) => {
+                        Ok(ValidationReport::failed(
+                            format!(
+                                "Testing fixture failed\n\
                         STDOUT:\n{out}\n\n\
                         STDERR:\n{err}\n\n\
                         Additional error:\n{r:?}"
-                        ),
-                        None,
-                    )),
+                            ),
+                            diagnostics,
+                        ))
+                    }
                     _ => Err(e.into()),
                 }
             }
@@ -69,10 +141,10 @@ async fn validate_code(
         .collect();
 
     if player_match_results.is_empty() {
-        return Ok((false, "The validation compilation & match succeeded, \
+        return Ok(ValidationReport::failed("The validation compilation & match succeeded, \
         but provided strategy was not found in the results\n\
         This usually means that your class does not implement gametheory.assignment2.Player interface"
-            .to_string(), None));
+            .to_string(), diagnostics));
     }
 
     if player_match_results.iter().any(|r| r.0.outcome.is_err()) {
@@ -91,21 +163,90 @@ async fn validate_code(
             writeln!(&mut res, "opponent result: {opponent:?}\n").unwrap();
         }
 
-        return Ok((false, res, Some(player_match_results)));
+        return Ok(ValidationReport {
+            valid: false,
+            message: res,
+            matches: Some(player_match_results),
+            diagnostics,
+        });
     }
 
-    Ok((true, "You pass!".to_string(), Some(player_match_results)))
+    Ok(ValidationReport {
+        valid: true,
+        message: "You pass!".to_string(),
+        matches: Some(player_match_results),
+        diagnostics,
+    })
 }
 
-#[instrument(skip(req))]
-pub async fn submit(mut req: Request<State>) -> tide::Result {
-    let execution_state = req.state().execution.clone();
+/// Resolves the requested JDK version, checking that it is available
+pub(crate) fn check_jdk(contest: &ContestConfig, jdk: Option<u32>) -> tide::Result<JdkVersion> {
+    let jdk = jdk.map(JdkVersion).unwrap_or(contest.default_jdk);
+    if contest.jdk_images.image(jdk).is_err() {
+        return Err(tide::http::Error::from_str(
+            StatusCode::BadRequest,
+            format!(
+                "JDK {jdk} is not available, choose one of: {}",
+                contest
+                    .jdk_images
+                    .versions()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        ));
+    }
+    Ok(jdk)
+}
 
-    let user_id = req.user_id().unwrap();
+/// Checks that the submissions of the user can be changed now
+pub(crate) fn check_open(contest: &ContestConfig, user: &user::Model) -> tide::Result<()> {
+    if contest
+        .schedule
+        .accepts_submissions(Utc::now(), user.deadline)
+    {
+        Ok(())
+    } else {
+        Err(tide::http::Error::from_str(
+            StatusCode::Forbidden,
+            "The contest is not accepting submissions",
+        ))
+    }
+}
 
-    info!("{user_id} uploads something");
+/// Checks that the user was not banned
+pub(crate) async fn check_not_banned(state: &State, user_id: &str) -> tide::Result<()> {
+    if state.db.get_user(user_id).await?.banned {
+        Err(tide::http::Error::from_str(
+            StatusCode::Forbidden,
+            "You are banned from the contest",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that the user can submit now, returning how the submission is accepted
+pub(crate) async fn check_accepted(state: &State, user_id: &str) -> tide::Result<Acceptance> {
+    check_not_banned(state, user_id).await?;
+    let user = state.db.get_user(user_id).await?;
 
-    let body = req.body_string().await?;
+    state
+        .contest
+        .accept_submission(Utc::now(), user.deadline)
+        .ok_or_else(|| {
+            tide::http::Error::from_str(
+                StatusCode::Forbidden,
+                "The contest is not accepting submissions",
+            )
+        })
+}
+
+/// Reads the uploaded code: either an archive with several source files or a single file
+pub(crate) async fn read_upload(
+    req: &mut Request<State>,
+) -> tide::Result<(String, Option<Vec<SourceFile>>)> {
+    let body = req.body_bytes().await?;
     if body.len() > UPLOAD_LIMIT {
         return Err(tide::http::Error::from_str(
             StatusCode::PayloadTooLarge,
@@ -113,26 +254,395 @@ pub async fn submit(mut req: Request<State>) -> tide::Result {
         ));
     }
 
-    let val_res = validate_code(execution_state, user_id.clone(), body.clone()).await?;
+    Ok(match unpack_sources(&body)? {
+        Some(files) => (join_sources(&files), Some(files)),
+        None => (
+            String::from_utf8(body).map_err(|_| {
+                tide::http::Error::from_str(StatusCode::BadRequest, "Upload is not a UTF-8 text")
+            })?,
+            None,
+        ),
+    })
+}
+
+#[derive(Deserialize, Debug)]
+struct SubmitQuery {
+    /// JDK version to compile and run the submission with, the contest default if not set
+    jdk: Option<u32>,
+}
+
+#[instrument(skip(req))]
+pub async fn submit(mut req: Request<State>) -> tide::Result {
+    let user_id = req.user_id().unwrap();
+
+    info!("{user_id} uploads something");
+
+    let acceptance = check_accepted(req.state(), &user_id).await?;
+    let query: SubmitQuery = req.query()?;
+    let jdk = check_jdk(&req.state().contest, query.jdk)?;
+
+    let (code, files) = read_upload(&mut req).await?;
+
+    if let Some(factor) = acceptance.late_penalty {
+        info!("{user_id} submits late, the grade is multiplied by {factor}");
+    }
+
+    let job_id = req
+        .state()
+        .validation
+        .enqueue(QueuedJob::Validation(PendingSubmission {
+            user_id,
+            code,
+            files,
+            jdk,
+            acceptance,
+        }))?;
 
-    req.state()
-        .db
+    Ok(tide::Response::builder(StatusCode::Accepted)
+        .body(Body::from_json(&SubmitResponse { job_id })?)
+        .build())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubmitResponse {
+    /// Id of the queued job, its progress is sent over /api/events and available at /api/jobs/:id
+    pub job_id: u64,
+}
+
+/// An uploaded submission waiting for validation
+#[derive(Debug)]
+pub struct PendingSubmission {
+    pub user_id: String,
+    pub code: String,
+    pub files: Option<Vec<SourceFile>>,
+    pub jdk: JdkVersion,
+    /// Decided at the upload time, the validation may finish after the deadline
+    pub acceptance: Acceptance,
+}
+
+/// Validates the submission and stores it along with the report, returning the submission id
+#[instrument(skip(state, on_status))]
+pub async fn validate_and_store(
+    state: &State,
+    submission: PendingSubmission,
+    on_status: impl Fn(JobStatus),
+) -> anyhow::Result<(i32, ValidationReport)> {
+    let PendingSubmission {
+        user_id,
+        code,
+        files,
+        jdk,
+        acceptance,
+    } = submission;
+
+    let val_res = validate_code(
+        state.execution.clone(),
+        user_id.clone(),
+        files
+            .clone()
+            .unwrap_or_else(|| single_file_sources(code.clone())),
+        jdk,
+        &on_status,
+    )
+    .await?;
+
+    let db = &state.db;
+    let id = db
         .add_submission(submission::Model {
             id: 0,
-            user_id,
-            code: body,
+            user_id: user_id.clone(),
+            code,
             datetime: DateTimeUtc::from(SystemTime::now()),
-            valid: val_res.0,
+            valid: val_res.valid,
+            files: files.map(|f| serde_json::to_string(&f)).transpose()?,
+            jdk_version: jdk.0 as i32,
+            report: Some(serde_json::to_string(&val_res)?),
+            late_penalty: acceptance.late_penalty,
+            ranked: acceptance.ranked,
+            invalidated: false,
         })
         .await?;
 
-    let body = Body::from_json(&val_res)?;
+    if val_res.valid && acceptance.ranked {
+        // a new valid submission replaces the chosen one, but does not bring a withdrawn user back
+        let mut user = db.get_user(&user_id).await?;
+        if user.active_submission.is_some() {
+            user.active_submission = None;
+            db.update_user(user).await?;
+        }
+    }
+
+    Ok((id, val_res))
+}
+
+#[instrument(skip(req))]
+pub async fn get_validation_job(req: Request<State>) -> tide::Result<Body> {
+    let user_id = req.user_id().unwrap();
+    let id = req.param("id")?;
+
+    let job = id
+        .parse()
+        .ok()
+        .and_then(|id| req.state().validation.get(id))
+        .filter(|j| j.user_id == user_id)
+        .ok_or_else(|| {
+            tide::http::Error::from_str(StatusCode::NotFound, format!("Job {id} not found"))
+        })?;
+
+    Body::from_json(&job)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JdkVersions {
+    pub default: JdkVersion,
+    pub available: Vec<JdkVersion>,
+}
+
+pub async fn get_jdk_versions(req: Request<State>) -> tide::Result<Body> {
+    let contest = &req.state().contest;
+
+    Body::from_json(&JdkVersions {
+        default: contest.default_jdk,
+        available: contest.jdk_images.versions().collect(),
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubmissionSummary {
+    pub id: i32,
+    pub datetime: DateTimeUtc,
+    pub valid: bool,
+    pub jdk_version: i32,
+    /// The multiplier of the grade for being late
+    pub late_penalty: Option<f64>,
+    /// Unranked submissions cannot play in the rounds
+    pub ranked: bool,
+    /// Invalidated by an admin, `valid` is still the validation result
+    pub invalidated: bool,
+}
+
+impl From<&submission::Model> for SubmissionSummary {
+    fn from(s: &submission::Model) -> Self {
+        SubmissionSummary {
+            id: s.id,
+            datetime: s.datetime,
+            valid: s.valid,
+            jdk_version: s.jdk_version,
+            late_penalty: s.late_penalty,
+            ranked: s.ranked,
+            invalidated: s.invalidated,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubmissionDetails {
+    #[serde(flatten)]
+    pub summary: SubmissionSummary,
+    pub files: Vec<SourceFile>,
+    /// `None` for the submissions made before the reports were stored
+    pub report: Option<ValidationReport>,
+}
+
+impl SubmissionDetails {
+    pub fn new(submission: &submission::Model) -> anyhow::Result<Self> {
+        Ok(SubmissionDetails {
+            summary: SubmissionSummary::from(submission),
+            files: submission_sources(submission)?,
+            report: submission
+                .report
+                .as_deref()
+                .map(serde_json::from_str)
+                .transpose()?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MySubmissions {
+    /// The submission playing in the rounds
+    pub active: Option<i32>,
+    pub withdrawn: bool,
+    /// Newest first
+    pub submissions: Vec<SubmissionSummary>,
+}
+
+#[instrument(skip(req))]
+pub async fn get_my_submissions(req: Request<State>) -> tide::Result<Body> {
+    let user_id = req.user_id().unwrap();
+    let db = &req.state().db;
 
-    Ok(tide::Response::builder(if val_res.0 {
-        StatusCode::Ok
+    let submissions = db.get_user_submissions(&user_id).await?;
+    let user = db.get_user(&user_id).await?;
+
+    let playable = |s: &submission::Model| s.is_valid() && s.ranked;
+    let latest_valid = submissions.iter().find(|s| playable(s)).map(|s| s.id);
+    let chosen = user
+        .active_submission
+        .filter(|id| submissions.iter().any(|s| s.id == *id && playable(s)));
+    let active = if user.withdrawn || user.banned {
+        None
     } else {
-        StatusCode::PreconditionFailed
+        chosen.or(latest_valid)
+    };
+
+    Body::from_json(&MySubmissions {
+        active,
+        withdrawn: user.withdrawn,
+        submissions: submissions.iter().map(SubmissionSummary::from).collect(),
     })
-    .body(body)
-    .build())
+}
+
+pub(crate) fn parse_submission_id(id: &str) -> tide::Result<i32> {
+    id.parse().map_err(|_| {
+        tide::http::Error::from_str(
+            StatusCode::BadRequest,
+            format!("Invalid submission id {id}"),
+        )
+    })
+}
+
+/// Fetches a submission of the user making the request
+pub(crate) async fn get_own_submission(
+    req: &Request<State>,
+    id: i32,
+) -> tide::Result<submission::Model> {
+    let user_id = req.user_id().unwrap();
+
+    match req.state().db.get_submission(id).await? {
+        Some(s) if s.user_id == user_id => Ok(s),
+        // don't tell whether someone else's submission exists
+        _ => Err(tide::http::Error::from_str(
+            StatusCode::NotFound,
+            format!("Submission {id} not found"),
+        )),
+    }
+}
+
+#[instrument(skip(req))]
+pub async fn get_my_submission(req: Request<State>) -> tide::Result<Body> {
+    let id = parse_submission_id(req.param("id")?)?;
+    let submission = get_own_submission(&req, id).await?;
+
+    Body::from_json(&SubmissionDetails::new(&submission)?)
+}
+
+#[derive(Deserialize, Debug)]
+struct DiffQuery {
+    from: String,
+    to: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FileChange {
+    Added,
+    Removed,
+    Modified,
+    Unchanged,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FileDiff {
+    pub name: String,
+    pub change: FileChange,
+    /// Unified diff of the file, empty if it is unchanged
+    pub diff: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubmissionDiff {
+    pub from: SubmissionSummary,
+    pub to: SubmissionSummary,
+    pub files: Vec<FileDiff>,
+}
+
+#[instrument(skip(req))]
+pub async fn diff_my_submissions(req: Request<State>) -> tide::Result<Body> {
+    let query: DiffQuery = req.query()?;
+    let from = get_own_submission(&req, parse_submission_id(&query.from)?).await?;
+    let to = get_own_submission(&req, parse_submission_id(&query.to)?).await?;
+
+    let mut files = BTreeMap::<String, (Option<String>, Option<String>)>::new();
+    for f in submission_sources(&from)? {
+        files.entry(f.name).or_default().0 = Some(f.code);
+    }
+    for f in submission_sources(&to)? {
+        files.entry(f.name).or_default().1 = Some(f.code);
+    }
+
+    let files = files
+        .into_iter()
+        .map(|(name, (old, new))| {
+            let change = match (&old, &new) {
+                (None, _) => FileChange::Added,
+                (_, None) => FileChange::Removed,
+                (Some(old), Some(new)) if old == new => FileChange::Unchanged,
+                _ => FileChange::Modified,
+            };
+            let (old, new) = (old.unwrap_or_default(), new.unwrap_or_default());
+            let diff = match change {
+                FileChange::Unchanged => String::new(),
+                _ => TextDiff::from_lines(&old, &new)
+                    .unified_diff()
+                    .header(&format!("{}/{name}", from.id), &format!("{}/{name}", to.id))
+                    .to_string(),
+            };
+
+            FileDiff { name, change, diff }
+        })
+        .collect();
+
+    Body::from_json(&SubmissionDiff {
+        from: SubmissionSummary::from(&from),
+        to: SubmissionSummary::from(&to),
+        files,
+    })
+}
+
+/// Makes the submission play in the rounds instead of the latest one, returning to the tournament if withdrawn
+#[instrument(skip(req))]
+pub async fn activate_submission(req: Request<State>) -> tide::Result {
+    let id = parse_submission_id(req.param("id")?)?;
+    let submission = get_own_submission(&req, id).await?;
+    let db = &req.state().db;
+    let mut user = db.get_user(&submission.user_id).await?;
+    check_open(&req.state().contest, &user)?;
+
+    if !submission.is_valid() {
+        return Err(tide::http::Error::from_str(
+            StatusCode::BadRequest,
+            format!("Submission {id} did not pass the validation or was invalidated"),
+        ));
+    }
+    if !submission.ranked {
+        return Err(tide::http::Error::from_str(
+            StatusCode::BadRequest,
+            format!("Submission {id} was made after the deadline and cannot play in the rounds"),
+        ));
+    }
+
+    user.active_submission = Some(id);
+    user.withdrawn = false;
+    db.update_user(user).await?;
+
+    info!("{} activates submission {id}", submission.user_id);
+
+    Ok(tide::Response::new(StatusCode::Ok))
+}
+
+/// Stops the user from playing in the rounds until they activate a submission
+#[instrument(skip(req))]
+pub async fn withdraw(req: Request<State>) -> tide::Result {
+    let user_id = req.user_id().unwrap();
+
+    let db = &req.state().db;
+    let mut user = db.get_user(&user_id).await?;
+    check_open(&req.state().contest, &user)?;
+    user.withdrawn = true;
+    db.update_user(user).await?;
+
+    info!("{user_id} withdraws from the tournament");
+
+    Ok(tide::Response::new(StatusCode::Ok))
 }
@@ -0,0 +1,235 @@
+use execution::matchmaker::SourceFile;
+use flate2::read::GzDecoder;
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::io::{self, Cursor, Read};
+use std::path::{Component, Path};
+use tide::StatusCode;
+
+const MAX_FILES: usize = 32;
+const MAX_FILE_SIZE: u64 = 256 * 1024;
+const MAX_TOTAL_SIZE: u64 = 1024 * 1024;
+/// Limits for all the entries of an archive, including the ones that are skipped
+const MAX_ENTRIES: usize = 256;
+const MAX_UNPACKED_SIZE: u64 = 8 * 1024 * 1024;
+
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn detect_archive(body: &[u8]) -> Option<ArchiveKind> {
+    if body.starts_with(b"PK\x03\x04") {
+        Some(ArchiveKind::Zip)
+    } else if body.starts_with(&[0x1f, 0x8b]) {
+        Some(ArchiveKind::TarGz)
+    } else if body.len() > 262 && &body[257..262] == b"ustar" {
+        Some(ArchiveKind::Tar)
+    } else {
+        None
+    }
+}
+
+fn bad_archive(message: impl Into<String>) -> tide::Error {
+    tide::http::Error::from_str(StatusCode::BadRequest, message.into())
+}
+
+fn too_large(message: impl Into<String>) -> tide::Error {
+    tide::http::Error::from_str(StatusCode::PayloadTooLarge, message.into())
+}
+
+fn too_many_entries() -> tide::Error {
+    too_large(format!("Archive contains more than {MAX_ENTRIES} entries"))
+}
+
+fn too_large_unpacked() -> tide::Error {
+    too_large(format!(
+        "Archive is larger than {MAX_UNPACKED_SIZE} bytes unpacked"
+    ))
+}
+
+/// Counts the bytes read through it and fails once there are more than `MAX_UNPACKED_SIZE`,
+/// so that the entries that are skipped are limited as well
+struct LimitedReader<'a, R> {
+    inner: R,
+    read: &'a Cell<u64>,
+}
+
+impl<R: Read> Read for LimitedReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.read.get() > MAX_UNPACKED_SIZE {
+            return Err(io::Error::other("the archive is too large"));
+        }
+        let n = self.inner.read(buf)?;
+        self.read.set(self.read.get() + n as u64);
+        Ok(n)
+    }
+}
+
+/// Collects the java files from the archive entries, enforcing the limits
+struct SourceCollector {
+    files: Vec<SourceFile>,
+    names: BTreeSet<String>,
+    total_size: u64,
+}
+
+impl SourceCollector {
+    fn new() -> Self {
+        Self {
+            files: Vec::new(),
+            names: BTreeSet::new(),
+            total_size: 0,
+        }
+    }
+
+    fn add(&mut self, path: &Path, size: u64, reader: impl Read) -> tide::Result<()> {
+        // the sources are flattened into a single package, so only the file name matters
+        let name = match path.file_name().and_then(|n| n.to_str()) {
+            Some(n) => n,
+            None => return Ok(()),
+        };
+        let hidden = path.components().any(|c| match c {
+            Component::Normal(c) => c.to_string_lossy().starts_with('.') || c == "__MACOSX",
+            _ => false,
+        });
+        if hidden || !name.ends_with(".java") {
+            return Ok(());
+        }
+
+        if self.files.len() >= MAX_FILES {
+            return Err(too_large(format!(
+                "Archive contains more than {MAX_FILES} java files"
+            )));
+        }
+        if size > MAX_FILE_SIZE {
+            return Err(too_large(format!(
+                "File {name} is larger than {MAX_FILE_SIZE} bytes"
+            )));
+        }
+        self.total_size += size;
+        if self.total_size > MAX_TOTAL_SIZE {
+            return Err(too_large(format!(
+                "Java files in the archive are larger than {MAX_TOTAL_SIZE} bytes in total"
+            )));
+        }
+        if !self.names.insert(name.to_string()) {
+            return Err(bad_archive(format!(
+                "Archive contains several files named {name}"
+            )));
+        }
+
+        // don't trust the size from the archive header
+        let mut code = String::new();
+        reader
+            .take(MAX_FILE_SIZE + 1)
+            .read_to_string(&mut code)
+            .map_err(|e| bad_archive(format!("Cannot read {name} as UTF-8 text: {e}")))?;
+        if code.len() as u64 > MAX_FILE_SIZE {
+            return Err(too_large(format!(
+                "File {name} is larger than {MAX_FILE_SIZE} bytes"
+            )));
+        }
+
+        self.files.push(SourceFile::new(name.to_string(), code));
+
+        Ok(())
+    }
+
+    fn finish(self) -> tide::Result<Vec<SourceFile>> {
+        if self.files.is_empty() {
+            return Err(bad_archive("Archive does not contain any java files"));
+        }
+        Ok(self.files)
+    }
+}
+
+fn unpack_zip(body: &[u8]) -> tide::Result<Vec<SourceFile>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(body))
+        .map_err(|e| bad_archive(format!("Cannot read the zip archive: {e}")))?;
+    if archive.len() > MAX_ENTRIES {
+        return Err(too_many_entries());
+    }
+
+    let mut collector = SourceCollector::new();
+    for i in 0..archive.len() {
+        let file = archive
+            .by_index(i)
+            .map_err(|e| bad_archive(format!("Cannot read the zip archive: {e}")))?;
+        if file.is_dir() {
+            continue;
+        }
+        let path = match file.enclosed_name() {
+            Some(p) => p.to_owned(),
+            None => continue,
+        };
+        let size = file.size();
+        collector.add(&path, size, file)?;
+    }
+
+    collector.finish()
+}
+
+fn unpack_tar(reader: impl Read) -> tide::Result<Vec<SourceFile>> {
+    let unpacked = Cell::new(0);
+    let mut archive = tar::Archive::new(LimitedReader {
+        inner: reader,
+        read: &unpacked,
+    });
+    let tar_error = |e: io::Error| {
+        if unpacked.get() > MAX_UNPACKED_SIZE {
+            too_large_unpacked()
+        } else {
+            bad_archive(format!("Cannot read the tar archive: {e}"))
+        }
+    };
+
+    let mut collector = SourceCollector::new();
+    let entries = archive.entries().map_err(tar_error)?;
+    for (i, entry) in entries.enumerate() {
+        if i >= MAX_ENTRIES {
+            return Err(too_many_entries());
+        }
+        let entry = entry.map_err(tar_error)?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path().map_err(tar_error)?.into_owned();
+        let size = entry.size();
+        let added = collector.add(&path, size, entry);
+        if unpacked.get() > MAX_UNPACKED_SIZE {
+            return Err(too_large_unpacked());
+        }
+        added?;
+    }
+
+    collector.finish()
+}
+
+/// Extracts the java files if the upload is a zip, tar or tar.gz archive.
+///
+/// Returns `None` if the upload is not an archive
+pub fn unpack_sources(body: &[u8]) -> tide::Result<Option<Vec<SourceFile>>> {
+    let files = match detect_archive(body) {
+        None => return Ok(None),
+        Some(ArchiveKind::Zip) => unpack_zip(body)?,
+        Some(ArchiveKind::Tar) => unpack_tar(body)?,
+        Some(ArchiveKind::TarGz) => unpack_tar(GzDecoder::new(body))?,
+    };
+
+    Ok(Some(files))
+}
+
+/// Joins the sources into a single text for displaying and comparison
+pub fn join_sources(files: &[SourceFile]) -> String {
+    let mut res = String::new();
+    for file in files {
+        writeln!(&mut res, "// ===== {} =====", file.name).unwrap();
+        res.push_str(&file.code);
+        if !file.code.ends_with('\n') {
+            res.push('\n');
+        }
+    }
+    res
+}
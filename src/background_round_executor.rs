@@ -1,36 +1,111 @@
-use crate::api::rounds::{compute_scoreboard, Scoreboard};
+use crate::api::rounds::scoreboard_ranks;
+use crate::contest::Phase;
+use crate::database::submission_sources;
+use crate::house::house_players;
+use crate::round_cache::RoundCache;
 use crate::State;
+use async_std::channel::{bounded, Receiver, Sender};
+use async_std::prelude::FutureExt;
+use chrono::Utc;
+use entity::sea_orm::prelude::DateTimeUtc;
+use execution::jdk::JdkVersion;
 use execution::matchmaker::{make_match_program, run_matched_program, RoundResult};
 use futures_signals::signal::Mutable;
 use futures_util::StreamExt;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tracing::{debug, error, info, instrument};
 
 const INTERVAL: Duration = Duration::from_secs(60);
 
+/// Lets the admins pause the rounds and play one right away, the rounds run again after a restart
+#[derive(Debug)]
+pub struct RoundControl {
+    paused: AtomicBool,
+    force_sender: Sender<()>,
+    force_receiver: Receiver<()>,
+}
+
+impl RoundControl {
+    pub fn new() -> Self {
+        let (force_sender, force_receiver) = bounded(1);
+
+        RoundControl {
+            paused: AtomicBool::new(false),
+            force_sender,
+            force_receiver,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed)
+    }
+
+    /// Plays a round once the current one is over, even if paused
+    pub fn force(&self) {
+        // a round already waiting to be forced is enough
+        let _ = self.force_sender.try_send(());
+    }
+}
+
 #[instrument(skip_all)]
 async fn run_one_round(state: &State) -> anyhow::Result<(BTreeMap<String, i32>, RoundResult)> {
     info!("Starting another round!");
 
     let strategies = state.db.get_active_submissions().await?;
+    let house = house_players(&state.db, &state.contest).await?;
 
-    info!("Found {} eligible strategies", strategies.len());
+    info!(
+        "Found {} eligible strategies and {} house strategies",
+        strategies.len(),
+        house.len()
+    );
 
+    // the house strategies have no submissions
     let user_strats: BTreeMap<String, i32> = strategies
         .iter()
         .map(|s| (s.user_id.clone(), s.id))
+        .chain(house.iter().map(|h| (h.name.clone(), 0)))
         .collect();
 
-    let matched = make_match_program(
-        &strategies
-            .into_iter()
-            .map(|s| (s.user_id, s.code))
-            .collect(),
-    )?;
-
-    let res = run_matched_program(state.execution.clone(), &matched).await?;
+    // each player is compiled for their own JDK
+    let mut players = strategies
+        .iter()
+        .map(|s| {
+            Ok((
+                s.user_id.clone(),
+                (submission_sources(s)?, JdkVersion(s.jdk_version as u32)),
+            ))
+        })
+        .collect::<anyhow::Result<HashMap<_, _>>>()?;
+
+    // all the players share a JVM, so it should be able to run all of them
+    let jdk = players
+        .values()
+        .map(|(_, jdk)| *jdk)
+        .chain(house.iter().map(|h| h.jdk))
+        .max()
+        .unwrap_or(state.contest.default_jdk);
+
+    players.extend(house.into_iter().map(|h| (h.name, (h.sources, h.jdk))));
+
+    let matched = make_match_program(&players, jdk)?;
+
+    let (res, failed) = run_matched_program(state.execution.clone(), matched).await?;
+    // the others still play, the ones that fail to compile only sit the round out
+    for (name, diagnostics) in failed {
+        let diagnostics: Vec<_> = diagnostics.iter().map(|d| d.to_string()).collect();
+        error!(
+            "The code of {name} does not compile, it is left out of the round:\n{}",
+            diagnostics.join("\n")
+        );
+    }
 
     Ok((user_strats, res))
 }
@@ -38,7 +113,7 @@ async fn run_one_round(state: &State) -> anyhow::Result<(BTreeMap<String, i32>,
 #[instrument(skip_all)]
 async fn run_and_submit_one_round(
     state: &State,
-    state_sender: &Arc<Mutable<Arc<(Vec<RoundResult>, Scoreboard)>>>,
+    state_sender: &Arc<Mutable<Arc<RoundCache>>>,
 ) -> anyhow::Result<()> {
     let now = Instant::now();
     let res = run_one_round(state).await;
@@ -51,13 +126,19 @@ async fn run_and_submit_one_round(
         Ok((strats, r)) => {
             info!("Regular round ended with {} matches", r.0.len());
 
-            state.db.add_round_result(&r, strats).await?;
-            let scoreboard = compute_scoreboard(&state.db).await?;
-
-            let (last_rounds, _) = state.db.get_last_rounds_results().await?;
+            let round_id = state.db.add_round_result(&r, strats, false).await?;
+            let cache = RoundCache::compute(&state.db, &state.contest).await?;
+            state
+                .db
+                .add_scoreboard_snapshot(
+                    round_id,
+                    cache.scoreboard.datetime,
+                    &scoreboard_ranks(&cache.scoreboard),
+                )
+                .await?;
 
             debug!("broadcasting state...");
-            state_sender.set(Arc::new((last_rounds, scoreboard)));
+            state_sender.set(Arc::new(cache));
             debug!("done broadcasting state!");
         }
         Err(err) => error!("An error occurred while running a regular round:\n{err:?}"),
@@ -65,13 +146,93 @@ async fn run_and_submit_one_round(
     Ok(())
 }
 
+/// The latest deadline extension not over yet, the final tournament waits for it
+async fn open_extension(state: &State) -> anyhow::Result<Option<DateTimeUtc>> {
+    let now = Utc::now();
+    Ok(state
+        .db
+        .get_all_users()
+        .await?
+        .into_iter()
+        .filter(|u| !u.banned)
+        .filter_map(|u| u.deadline)
+        .filter(|&deadline| now < deadline)
+        .max())
+}
+
+/// Plays the rounds of the final tournament left after the `played` ones, then publishes the standings
+#[instrument(skip_all)]
+async fn run_final_tournament(
+    state: &State,
+    state_sender: &Arc<Mutable<Arc<RoundCache>>>,
+    played: u64,
+) -> anyhow::Result<()> {
+    let total = state.contest.final_rounds;
+    info!("Playing the final tournament, {played} of {total} rounds are already played");
+
+    for i in played..total {
+        let (strats, r) = run_one_round(state).await?;
+        state.db.add_round_result(&r, strats, true).await?;
+        info!(
+            "Final round {} of {total} ended with {} matches",
+            i + 1,
+            r.0.len()
+        );
+    }
+
+    let cache = RoundCache::compute(&state.db, &state.contest).await?;
+    state_sender.set(Arc::new(cache));
+    info!("The final tournament is over");
+
+    Ok(())
+}
+
 pub async fn background_round_executor(
     state: &State,
-    state_sender: Arc<Mutable<Arc<(Vec<RoundResult>, Scoreboard)>>>,
+    state_sender: Arc<Mutable<Arc<RoundCache>>>,
 ) -> anyhow::Result<()> {
     let mut interval = async_std::stream::interval(INTERVAL);
-    while (interval.next().await).is_some() {
-        let err = run_and_submit_one_round(state, &state_sender).await;
+    loop {
+        let tick = async { interval.next().await.map(|_| false) };
+        let forced = async { state.rounds.force_receiver.recv().await.ok().map(|_| true) };
+        let forced = match tick.race(forced).await {
+            Some(forced) => forced,
+            None => break,
+        };
+
+        let played = state.db.count_final_rounds().await?;
+        let phase = state
+            .contest
+            .schedule
+            .phase(Utc::now(), played, state.contest.final_rounds);
+
+        // the scoreboard freezes and thaws with the phase, even if no round is played
+        if phase != state_sender.lock_ref().phase() {
+            info!("The contest is now in the {phase:?} phase");
+            match RoundCache::compute(&state.db, &state.contest).await {
+                Ok(cache) => state_sender.set(Arc::new(cache)),
+                Err(e) => error!("{:?}", e),
+            }
+        }
+
+        if state.rounds.is_paused() && !forced {
+            continue;
+        }
+
+        let err = match phase {
+            Phase::NotStarted | Phase::Closed => Ok(()),
+            Phase::Open | Phase::Frozen => run_and_submit_one_round(state, &state_sender).await,
+            Phase::Final => match open_extension(state).await {
+                // the regular rounds go on until the extended submissions are in
+                Ok(Some(until)) => {
+                    info!("The final tournament waits for the deadline extensions until {until}");
+                    run_and_submit_one_round(state, &state_sender).await
+                }
+                // a failed round is played again on the next tick
+                Ok(None) => run_final_tournament(state, &state_sender, played).await,
+                Err(e) => Err(e),
+            },
+        };
         if let Err(e) = err {
             error!("{:?}", e)
         }
@@ -0,0 +1,231 @@
+use crate::house::is_house;
+use crate::similarity::SourceRef;
+use execution::matchmaker::RoundResult;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Pairs of submissions that met fewer common opponents are not compared
+const MIN_COMMON_MATCHES: usize = 3;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BehaviourPair {
+    pub first: SourceRef,
+    pub second: SourceRef,
+    /// Mean share of identical moves against the same opponents
+    pub similarity: f64,
+    pub common_matches: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BehaviourReport {
+    /// Groups of submissions connected by pairs with similarity above the threshold
+    pub clusters: Vec<Vec<SourceRef>>,
+    pub pairs: Vec<BehaviourPair>,
+}
+
+/// Moves made by a submission, keyed by the round and the opponent with their submission
+///
+/// The house strategies all play as submission `0`, the name tells them apart
+type Signature<'a> = HashMap<(usize, &'a str, i32), &'a [i32]>;
+
+fn moves_agreement(a: &[i32], b: &[i32]) -> f64 {
+    let len = a.len().max(b.len());
+    if len == 0 {
+        return 1.0;
+    }
+    let same = a.iter().zip(b.iter()).filter(|(a, b)| a == b).count();
+    same as f64 / len as f64
+}
+
+fn compare(
+    (a_user, a): (&str, &Signature),
+    (b_user, b): (&str, &Signature),
+) -> Option<(f64, usize)> {
+    let agreements: Vec<f64> = a
+        .iter()
+        // the match against each other tells nothing
+        .filter(|((_, opponent, _), _)| *opponent != a_user && *opponent != b_user)
+        .filter_map(|(key, a_moves)| b.get(key).map(|b_moves| moves_agreement(a_moves, b_moves)))
+        .collect();
+
+    if agreements.len() < MIN_COMMON_MATCHES {
+        return None;
+    }
+
+    let mean = agreements.iter().sum::<f64>() / agreements.len() as f64;
+    Some((mean, agreements.len()))
+}
+
+fn find_root(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[i] = root;
+    root
+}
+
+/// Builds behaviour signatures of the submissions from the rounds
+/// (given with the submission ids of their participants) and finds the ones playing the same way.
+///
+/// Submissions of the same user are not compared with each other.
+pub fn compute_behaviour_report(
+    rounds: &[(BTreeMap<String, i32>, RoundResult)],
+    min_similarity: f64,
+) -> BehaviourReport {
+    let mut signatures = BTreeMap::<(&str, i32), Signature>::new();
+
+    for (round_index, (participants, round)) in rounds.iter().enumerate() {
+        for m in &round.0 {
+            let (p1, p2) = (&m.player1, &m.player2);
+            let (s1, s2) = match (
+                participants.get(&p1.player_name),
+                participants.get(&p2.player_name),
+            ) {
+                (Some(&s1), Some(&s2)) => (s1, s2),
+                _ => continue,
+            };
+
+            signatures
+                .entry((p1.player_name.as_str(), s1))
+                .or_default()
+                .insert((round_index, &p2.player_name, s2), p1.moves.as_slice());
+            signatures
+                .entry((p2.player_name.as_str(), s2))
+                .or_default()
+                .insert((round_index, &p1.player_name, s1), p2.moves.as_slice());
+        }
+    }
+
+    let signatures: Vec<_> = signatures.into_iter().collect();
+    let source = |i: usize| {
+        let (name, id) = signatures[i].0;
+        if is_house(name) {
+            SourceRef::Dummy {
+                name: name.to_string(),
+            }
+        } else {
+            SourceRef::Submission {
+                id,
+                user_id: name.to_string(),
+            }
+        }
+    };
+
+    let mut parents: Vec<usize> = (0..signatures.len()).collect();
+    let mut pairs = Vec::new();
+
+    for i in 0..signatures.len() {
+        for j in i + 1..signatures.len() {
+            let ((a_user, _), a) = &signatures[i];
+            let ((b_user, _), b) = &signatures[j];
+            if a_user == b_user {
+                continue;
+            }
+
+            let (similarity, common_matches) = match compare((a_user, a), (b_user, b)) {
+                Some(r) if r.0 >= min_similarity => r,
+                _ => continue,
+            };
+
+            let (root_i, root_j) = (find_root(&mut parents, i), find_root(&mut parents, j));
+            parents[root_i] = root_j;
+
+            pairs.push(BehaviourPair {
+                first: source(i),
+                second: source(j),
+                similarity,
+                common_matches,
+            });
+        }
+    }
+
+    let mut clusters = BTreeMap::<usize, Vec<SourceRef>>::new();
+    for i in 0..signatures.len() {
+        let root = find_root(&mut parents, i);
+        clusters.entry(root).or_default().push(source(i));
+    }
+    let mut clusters: Vec<_> = clusters.into_values().filter(|c| c.len() > 1).collect();
+    clusters.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+
+    pairs.sort_by(|a, b| {
+        b.similarity
+            .partial_cmp(&a.similarity)
+            .unwrap()
+            .then_with(|| a.first.cmp(&b.first))
+            .then_with(|| a.second.cmp(&b.second))
+    });
+
+    BehaviourReport { clusters, pairs }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use execution::matchmaker::{MatchResult, PlayerResult};
+
+    fn play(player: &str, opponent: &str, moves: &[i32]) -> MatchResult {
+        let result = |name: &str| PlayerResult {
+            player_name: name.to_string(),
+            outcome: Ok(0.0),
+            moves: moves.to_vec(),
+        };
+        MatchResult {
+            moves: moves.len() as u32,
+            player1: result(player),
+            player2: result(opponent),
+        }
+    }
+
+    #[test]
+    fn house_opponents_are_told_apart() {
+        let participants: BTreeMap<_, _> = [
+            ("alice", 1),
+            ("bob", 2),
+            ("house:x", 0),
+            ("house:y", 0),
+            ("house:z", 0),
+        ]
+        .into_iter()
+        .map(|(name, id)| (name.to_string(), id))
+        .collect();
+        let matches = ["alice", "bob"]
+            .into_iter()
+            .flat_map(|user| {
+                [
+                    play(user, "house:x", &[1, 2, 3]),
+                    play(user, "house:y", &[2, 3, 1]),
+                    play(user, "house:z", &[3, 1, 2]),
+                ]
+            })
+            .collect();
+
+        let report = compute_behaviour_report(&[(participants, RoundResult(matches))], 0.9);
+
+        let users: Vec<_> = report
+            .pairs
+            .iter()
+            .filter(|p| matches!(p.first, SourceRef::Submission { .. }))
+            .map(|p| (&p.first, &p.second, p.common_matches))
+            .collect();
+        assert_eq!(
+            users,
+            [(
+                &SourceRef::Submission {
+                    id: 1,
+                    user_id: "alice".to_string()
+                },
+                &SourceRef::Submission {
+                    id: 2,
+                    user_id: "bob".to_string()
+                },
+                3
+            )]
+        );
+        assert!(report
+            .clusters
+            .iter()
+            .flatten()
+            .all(|s| !matches!(s, SourceRef::Submission { id: 0, .. })));
+    }
+}
@@ -0,0 +1,410 @@
+use crate::grading::Rubric;
+use entity::sea_orm::prelude::DateTimeUtc;
+use execution::jdk::{JdkImages, JdkVersion};
+use execution::matchmaker::DUMMY_STRATS;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::env;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    NotStarted,
+    /// Submissions are accepted and the rounds are played
+    Open,
+    /// As open, but the public scoreboard does not change any more
+    Frozen,
+    /// The deadline has passed, the final tournament is played once the deadline extensions are over too
+    Final,
+    /// The official standings are out
+    Closed,
+}
+
+/// Times the phases start at, a phase without a time is skipped
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Schedule {
+    pub start: Option<DateTimeUtc>,
+    pub freeze: Option<DateTimeUtc>,
+    /// Submission deadline, the final tournament starts after it
+    pub deadline: Option<DateTimeUtc>,
+}
+
+impl Schedule {
+    /// The phase at `now`, the final tournament is over after `final_rounds` of `total_final_rounds`
+    pub fn phase(&self, now: DateTimeUtc, final_rounds: u64, total_final_rounds: u64) -> Phase {
+        let passed = |t: Option<DateTimeUtc>| t.is_some_and(|t| t <= now);
+
+        if self.start.is_some() && !passed(self.start) {
+            Phase::NotStarted
+        } else if passed(self.deadline) {
+            if final_rounds < total_final_rounds {
+                Phase::Final
+            } else {
+                Phase::Closed
+            }
+        } else if passed(self.freeze) {
+            Phase::Frozen
+        } else {
+            Phase::Open
+        }
+    }
+
+    /// Whether the submissions can be made or changed at `now`, `deadline` overrides the one of the schedule
+    pub fn accepts_submissions(&self, now: DateTimeUtc, deadline: Option<DateTimeUtc>) -> bool {
+        self.start.is_none_or(|t| t <= now) && deadline.or(self.deadline).is_none_or(|t| now < t)
+    }
+}
+
+/// How the submissions made after the deadline of their author are treated
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum LatePolicy {
+    /// The late submissions are not accepted
+    #[default]
+    Reject,
+    /// The late submissions are accepted, the grade of their author is multiplied by `factor`
+    Penalty { factor: f64 },
+    /// The late submissions are accepted, but do not play in the ranked rounds
+    Unranked,
+}
+
+/// How a new submission is accepted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Acceptance {
+    /// The multiplier of the grade for being late, `None` for the submissions made in time
+    pub late_penalty: Option<f64>,
+    /// The submission can play in the rounds
+    pub ranked: bool,
+}
+
+/// Which rounds the scoreboard is computed over
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "by", rename_all = "lowercase")]
+pub enum ScoringWindow {
+    /// The last `count` rounds
+    Rounds { count: u64 },
+    /// The rounds played in the last `minutes`
+    Time { minutes: u32 },
+}
+
+/// How the matches in which a player failed are counted
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ErrorPolicy {
+    /// The opponent of the failed player gets `score`, the failed player gets 0
+    Forfeit { score: f64 },
+    /// The failed player gets `score`, the match is not counted for the opponent
+    Penalty { score: f64 },
+    /// The match is counted for neither of the players
+    Exclude,
+    /// Both players get the score they had before the failure
+    Partial,
+}
+
+impl Default for ErrorPolicy {
+    fn default() -> Self {
+        ErrorPolicy::Penalty { score: 0.0 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ScoringConfig {
+    pub window: ScoringWindow,
+    /// Number of rounds after which the weight of a round halves, all the rounds weigh the same if not set
+    pub half_life: Option<f64>,
+    /// Count only the results of the active submission of each player, not of their earlier ones
+    pub active_submission_only: bool,
+    /// Mark the neighbours with overlapping confidence intervals as tied, they share the rank
+    pub tie_indistinguishable: bool,
+    pub error_policy: ErrorPolicy,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        ScoringConfig {
+            window: ScoringWindow::Rounds { count: 20 },
+            half_life: None,
+            active_submission_only: false,
+            tie_indistinguishable: false,
+            error_policy: ErrorPolicy::default(),
+        }
+    }
+}
+
+impl ScoringConfig {
+    /// Weight of the results of a round, `age` being the number of the rounds played after it
+    pub fn weight(&self, age: usize) -> f64 {
+        match self.half_life {
+            Some(half_life) => 0.5f64.powf(age as f64 / half_life),
+            None => 1.0,
+        }
+    }
+}
+
+/// Contest settings, loaded from the JSON file at CONTEST_CONFIG (everything has a default)
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ContestConfig {
+    /// Docker images of the JDK versions the submissions can choose from
+    pub jdk_images: JdkImages,
+    /// JDK version for the submissions that do not choose one
+    pub default_jdk: JdkVersion,
+    pub scoring: ScoringConfig,
+    pub schedule: Schedule,
+    /// Number of the rounds in the final tournament, each pairing plays once in every round
+    pub final_rounds: u64,
+    pub late_policy: LatePolicy,
+    /// How the grades are computed from the standings
+    pub rubric: Rubric,
+    /// Dummy strategies playing in every round as the house strategies, to compare the users with
+    pub house_strategies: Vec<String>,
+}
+
+impl Default for ContestConfig {
+    fn default() -> Self {
+        ContestConfig {
+            jdk_images: JdkImages::default(),
+            default_jdk: JdkVersion(8),
+            scoring: ScoringConfig::default(),
+            schedule: Schedule::default(),
+            final_rounds: 50,
+            late_policy: LatePolicy::default(),
+            rubric: Rubric::default(),
+            house_strategies: Vec::new(),
+        }
+    }
+}
+
+impl ContestConfig {
+    /// How a submission made at `now` is accepted, `deadline` overrides the deadline of the schedule for its author
+    ///
+    /// The submissions made before the deadline of their author can play in the rounds, even after the deadline
+    /// of the schedule (e.g. with an extension). The late ones play only with a penalty and only until the deadline
+    /// of the schedule. `None` if the submission is not accepted.
+    pub fn accept_submission(
+        &self,
+        now: DateTimeUtc,
+        deadline: Option<DateTimeUtc>,
+    ) -> Option<Acceptance> {
+        if self.schedule.start.is_some_and(|t| now < t) {
+            return None;
+        }
+
+        if deadline.or(self.schedule.deadline).is_none_or(|t| now < t) {
+            return Some(Acceptance {
+                late_penalty: None,
+                ranked: true,
+            });
+        }
+        match self.late_policy {
+            LatePolicy::Reject => None,
+            LatePolicy::Penalty { factor } => Some(Acceptance {
+                late_penalty: Some(factor),
+                ranked: self.schedule.deadline.is_none_or(|t| now < t),
+            }),
+            LatePolicy::Unranked => Some(Acceptance {
+                late_penalty: None,
+                ranked: false,
+            }),
+        }
+    }
+
+    pub fn load() -> anyhow::Result<Self> {
+        let config: ContestConfig = match env::var("CONTEST_CONFIG") {
+            Ok(path) => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+            Err(_) => Default::default(),
+        };
+
+        config.jdk_images.image(config.default_jdk)?;
+        if let Some(half_life) = config.scoring.half_life {
+            anyhow::ensure!(
+                half_life > 0.0,
+                "The half-life of the rounds should be positive"
+            );
+        }
+
+        if let LatePolicy::Penalty { factor } = config.late_policy {
+            anyhow::ensure!(
+                (0.0..=1.0).contains(&factor),
+                "The late penalty factor should be between 0 and 1"
+            );
+        }
+
+        for name in &config.house_strategies {
+            anyhow::ensure!(
+                DUMMY_STRATS.iter().any(|(dummy, _)| dummy == name),
+                "Unknown dummy strategy {name} among the house strategies"
+            );
+        }
+        anyhow::ensure!(
+            config.rubric.max_points > 0.0,
+            "The maximum grade should be positive"
+        );
+
+        let times = [
+            config.schedule.start,
+            config.schedule.freeze,
+            config.schedule.deadline,
+        ];
+        anyhow::ensure!(
+            times.iter().flatten().tuple_windows().all(|(a, b)| a <= b),
+            "The start, freeze and deadline times should go in that order"
+        );
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn time(s: &str) -> DateTimeUtc {
+        s.parse().unwrap()
+    }
+
+    fn schedule() -> Schedule {
+        Schedule {
+            start: Some(time("2022-04-01T10:00:00Z")),
+            freeze: Some(time("2022-04-10T10:00:00Z")),
+            deadline: Some(time("2022-04-11T10:00:00Z")),
+        }
+    }
+
+    #[test]
+    fn phases_start_exactly_at_their_times() {
+        let schedule = schedule();
+        let second = Duration::seconds(1);
+        let (start, freeze, deadline) = (
+            schedule.start.unwrap(),
+            schedule.freeze.unwrap(),
+            schedule.deadline.unwrap(),
+        );
+
+        assert_eq!(schedule.phase(start - second, 0, 5), Phase::NotStarted);
+        assert_eq!(schedule.phase(start, 0, 5), Phase::Open);
+        assert_eq!(schedule.phase(freeze - second, 0, 5), Phase::Open);
+        assert_eq!(schedule.phase(freeze, 0, 5), Phase::Frozen);
+        assert_eq!(schedule.phase(deadline - second, 0, 5), Phase::Frozen);
+        assert_eq!(schedule.phase(deadline, 0, 5), Phase::Final);
+    }
+
+    #[test]
+    fn closed_after_the_final_rounds() {
+        let schedule = schedule();
+        let deadline = schedule.deadline.unwrap();
+
+        assert_eq!(schedule.phase(deadline, 4, 5), Phase::Final);
+        assert_eq!(schedule.phase(deadline, 5, 5), Phase::Closed);
+        assert_eq!(schedule.phase(deadline, 0, 0), Phase::Closed);
+    }
+
+    #[test]
+    fn phases_without_a_time_are_skipped() {
+        let now = time("2022-04-05T10:00:00Z");
+        assert_eq!(Schedule::default().phase(now, 0, 5), Phase::Open);
+
+        let schedule = Schedule {
+            freeze: None,
+            ..schedule()
+        };
+        assert_eq!(
+            schedule.phase(schedule.deadline.unwrap() - Duration::seconds(1), 0, 5),
+            Phase::Open
+        );
+    }
+
+    fn config(late_policy: LatePolicy) -> ContestConfig {
+        ContestConfig {
+            schedule: schedule(),
+            late_policy,
+            ..ContestConfig::default()
+        }
+    }
+
+    const ON_TIME: Acceptance = Acceptance {
+        late_penalty: None,
+        ranked: true,
+    };
+
+    #[test]
+    fn accepts_in_time() {
+        let config = config(LatePolicy::Reject);
+        let deadline = config.schedule.deadline.unwrap();
+
+        assert_eq!(
+            config.accept_submission(config.schedule.start.unwrap() - Duration::seconds(1), None),
+            None
+        );
+        assert_eq!(
+            config.accept_submission(deadline - Duration::seconds(1), None),
+            Some(ON_TIME)
+        );
+        assert_eq!(config.accept_submission(deadline, None), None);
+    }
+
+    #[test]
+    fn late_submissions() {
+        let late = schedule().deadline.unwrap() + Duration::hours(1);
+
+        assert_eq!(
+            config(LatePolicy::Reject).accept_submission(late, None),
+            None
+        );
+        assert_eq!(
+            config(LatePolicy::Penalty { factor: 0.5 }).accept_submission(late, None),
+            Some(Acceptance {
+                late_penalty: Some(0.5),
+                ranked: false,
+            })
+        );
+        assert_eq!(
+            config(LatePolicy::Unranked).accept_submission(late, None),
+            Some(Acceptance {
+                late_penalty: None,
+                ranked: false,
+            })
+        );
+    }
+
+    #[test]
+    fn earlier_deadline_of_the_user() {
+        let config = config(LatePolicy::Penalty { factor: 0.5 });
+        let deadline = config.schedule.deadline.unwrap() - Duration::days(1);
+
+        assert_eq!(
+            config.accept_submission(deadline, Some(deadline)),
+            Some(Acceptance {
+                late_penalty: Some(0.5),
+                ranked: true,
+            })
+        );
+    }
+
+    #[test]
+    fn extension_past_the_deadline_of_the_schedule() {
+        let extension = schedule().deadline.unwrap() + Duration::days(2);
+        let now = extension - Duration::hours(1);
+
+        for policy in [
+            LatePolicy::Reject,
+            LatePolicy::Penalty { factor: 0.5 },
+            LatePolicy::Unranked,
+        ] {
+            let config = config(policy);
+            assert_eq!(
+                config.accept_submission(now, Some(extension)),
+                Some(ON_TIME)
+            );
+            assert!(config.schedule.accepts_submissions(now, Some(extension)));
+            assert!(!config.schedule.accepts_submissions(now, None));
+        }
+        assert_eq!(
+            config(LatePolicy::Reject).accept_submission(extension, Some(extension)),
+            None
+        );
+    }
+}
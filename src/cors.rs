@@ -49,10 +49,7 @@ impl CorsMiddleware {
     /// Set `allow_credentials` and return new Cors
     #[must_use]
     pub fn allow_credentials(mut self, allow_credentials: bool) -> Self {
-        self.allow_credentials = match allow_credentials.to_string().parse() {
-            Ok(header) => Some(header),
-            Err(_) => None,
-        };
+        self.allow_credentials = allow_credentials.to_string().parse().ok();
         self
     }
 
@@ -155,7 +152,7 @@ impl<State: Clone + Send + Sync + 'static> Middleware<State> for CorsMiddleware
 
         debug!("Origin is {}", origin);
 
-        if !self.is_valid_origin(&origin) {
+        if !self.is_valid_origin(origin) {
             error!("Origin is invalid, returning 403");
             return Ok(tide::http::Response::new(StatusCode::Forbidden).into());
         }
@@ -170,7 +167,7 @@ impl<State: Clone + Send + Sync + 'static> Middleware<State> for CorsMiddleware
 
         response.insert_header(
             headers::ACCESS_CONTROL_ALLOW_ORIGIN,
-            self.response_origin(&origin),
+            self.response_origin(origin),
         );
 
         if let Some(allow_credentials) = &self.allow_credentials {
@@ -244,6 +241,7 @@ impl From<Vec<&str>> for Origin {
 }
 
 #[cfg(test)]
+#[allow(unexpected_cfgs)]
 mod test {
     use super::*;
     use tide::http::headers::{self, HeaderValue};
@@ -1,21 +1,54 @@
+use crate::contest::ScoringWindow;
 use anyhow::anyhow;
 use entity::sea_orm::sea_query::{Alias, Expr, Function, SimpleExpr};
 use entity::sea_orm::{
-    ActiveValue, ColumnTrait, Condition, DatabaseConnection, EntityTrait, IntoActiveModel,
-    IntoSimpleExpr, JoinType, QueryFilter, QueryOrder, QuerySelect,
+    ActiveValue, ColumnTrait, Condition, DatabaseConnection, EntityTrait, FromQueryResult,
+    IntoActiveModel, IntoSimpleExpr, JoinType, PaginatorTrait, QueryFilter, QueryOrder,
+    QuerySelect, TransactionTrait,
 };
-use std::collections::BTreeMap;
+use itertools::Itertools;
+use std::collections::{BTreeMap, HashMap};
 use std::time::SystemTime;
 use tracing::{info, instrument};
 
+use entity::sea_orm;
 use entity::sea_orm::prelude::DateTimeUtc;
-use entity::{round_result, submission};
-use execution::matchmaker::RoundResult;
+use entity::{
+    house_strategy, match_result, player_result, round, scoreboard_entry, submission, user,
+};
+use execution::matchmaker::{
+    single_file_sources, MatchResult, PlayerResult, RoundResult, SourceFile,
+};
 use submission::Entity as Submission;
+use user::Entity as User;
 
 #[derive(Clone, Debug)]
 pub struct Database(pub DatabaseConnection);
 
+/// Returns the source files of the submission
+pub fn submission_sources(submission: &submission::Model) -> anyhow::Result<Vec<SourceFile>> {
+    Ok(match &submission.files {
+        Some(files) => serde_json::from_str(files)?,
+        None => single_file_sources(submission.code.clone()),
+    })
+}
+
+/// Rebuilds the result of the player from the stored row
+fn stored_player_result(r: &player_result::Model) -> anyhow::Result<PlayerResult> {
+    Ok(PlayerResult {
+        player_name: r.user_id.clone(),
+        outcome: r.outcome(),
+        moves: serde_json::from_str(&r.moves)?,
+    })
+}
+
+#[derive(FromQueryResult, Debug)]
+struct Participant {
+    round_id: i32,
+    user_id: String,
+    submission_id: i32,
+}
+
 impl Database {
     #[instrument]
     pub async fn add_submission(&self, submission: submission::Model) -> anyhow::Result<i32> {
@@ -28,12 +61,15 @@ impl Database {
         Ok(id)
     }
 
+    /// Returns the latest valid ranked submission of each user, the invalidated ones are not valid
     #[instrument]
-    pub async fn get_active_submissions(&self) -> Result<Vec<submission::Model>, anyhow::Error> {
+    async fn get_latest_valid_submissions(&self) -> anyhow::Result<Vec<submission::Model>> {
         let mut datetime_q = Submission::find()
             .select_only()
             .column(submission::Column::UserId)
             .filter(submission::Column::Valid.eq(true))
+            .filter(submission::Column::Ranked.eq(true))
+            .filter(submission::Column::Invalidated.eq(false))
             .group_by(submission::Column::UserId);
 
         let dt = Alias::new("dt");
@@ -66,48 +102,626 @@ impl Database {
         Ok(q.all(&self.0).await?)
     }
 
+    /// Returns the submissions taking part in the rounds: the one chosen by each user
+    /// or their latest valid one, except for the withdrawn and the banned users
+    #[instrument]
+    pub async fn get_active_submissions(&self) -> anyhow::Result<Vec<submission::Model>> {
+        let users: HashMap<_, _> = User::find()
+            .all(&self.0)
+            .await?
+            .into_iter()
+            .map(|u| (u.user_id.clone(), u))
+            .collect();
+
+        let chosen: HashMap<_, _> = Submission::find()
+            .filter(
+                submission::Column::Id
+                    .is_in(users.values().filter_map(|u| u.active_submission))
+                    .and(submission::Column::Valid.eq(true))
+                    .and(submission::Column::Ranked.eq(true))
+                    .and(submission::Column::Invalidated.eq(false)),
+            )
+            .all(&self.0)
+            .await?
+            .into_iter()
+            .map(|s| (s.id, s))
+            .collect();
+
+        Ok(self
+            .get_latest_valid_submissions()
+            .await?
+            .into_iter()
+            .filter_map(|latest| match users.get(&latest.user_id) {
+                Some(u) if u.withdrawn || u.banned => None,
+                Some(user::Model {
+                    active_submission: Some(id),
+                    ..
+                }) => Some(chosen.get(id).cloned().unwrap_or(latest)),
+                _ => Some(latest),
+            })
+            .collect())
+    }
+
+    #[instrument]
+    pub async fn get_user(&self, user_id: &str) -> anyhow::Result<user::Model> {
+        Ok(User::find_by_id(user_id.to_string())
+            .one(&self.0)
+            .await?
+            .unwrap_or_else(|| user::Model {
+                user_id: user_id.to_string(),
+                active_submission: None,
+                withdrawn: false,
+                sparring_partner: false,
+                deadline: None,
+                banned: false,
+            }))
+    }
+
+    /// Returns the ids of the users who opted in as sparring partners
+    #[instrument]
+    pub async fn get_sparring_partners(&self) -> anyhow::Result<Vec<String>> {
+        Ok(User::find()
+            .filter(user::Column::SparringPartner.eq(true))
+            .order_by_asc(user::Column::UserId)
+            .all(&self.0)
+            .await?
+            .into_iter()
+            .map(|u| u.user_id)
+            .collect())
+    }
+
+    #[instrument]
+    pub async fn update_user(&self, user: user::Model) -> anyhow::Result<()> {
+        // in one transaction, so that the concurrent updates of a new user do not both insert
+        let txn = self.0.begin().await?;
+        let exists = User::find_by_id(user.user_id.clone())
+            .one(&txn)
+            .await?
+            .is_some();
+
+        let am = user::ActiveModel {
+            user_id: ActiveValue::Set(user.user_id),
+            active_submission: ActiveValue::Set(user.active_submission),
+            withdrawn: ActiveValue::Set(user.withdrawn),
+            sparring_partner: ActiveValue::Set(user.sparring_partner),
+            deadline: ActiveValue::Set(user.deadline),
+            banned: ActiveValue::Set(user.banned),
+        };
+        if exists {
+            User::update(am).exec(&txn).await?;
+        } else {
+            User::insert(am).exec(&txn).await?;
+        }
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    /// Returns the users who changed any of their settings or were changed by the admins
+    #[instrument]
+    pub async fn get_all_users(&self) -> anyhow::Result<Vec<user::Model>> {
+        Ok(User::find()
+            .order_by_asc(user::Column::UserId)
+            .all(&self.0)
+            .await?)
+    }
+
+    /// Marks the submission as invalidated or not, returning whether it exists
+    #[instrument]
+    pub async fn set_submission_invalidated(
+        &self,
+        id: i32,
+        invalidated: bool,
+    ) -> anyhow::Result<bool> {
+        let res = Submission::update_many()
+            .col_expr(submission::Column::Invalidated, Expr::value(invalidated))
+            .filter(submission::Column::Id.eq(id))
+            .exec(&self.0)
+            .await?;
+
+        Ok(res.rows_affected > 0)
+    }
+
+    #[instrument]
+    pub async fn get_house_strategies(&self) -> anyhow::Result<Vec<house_strategy::Model>> {
+        Ok(house_strategy::Entity::find()
+            .order_by_asc(house_strategy::Column::Name)
+            .all(&self.0)
+            .await?)
+    }
+
+    #[instrument(skip(strategy))]
+    pub async fn add_house_strategy(&self, strategy: house_strategy::Model) -> anyhow::Result<i32> {
+        let mut am = strategy.into_active_model();
+        am.id = ActiveValue::NotSet;
+
+        Ok(house_strategy::Entity::insert(am)
+            .exec(&self.0)
+            .await?
+            .last_insert_id)
+    }
+
+    /// Returns whether there was such a house strategy
+    #[instrument]
+    pub async fn delete_house_strategy(&self, id: i32) -> anyhow::Result<bool> {
+        let res = house_strategy::Entity::delete_many()
+            .filter(house_strategy::Column::Id.eq(id))
+            .exec(&self.0)
+            .await?;
+
+        Ok(res.rows_affected > 0)
+    }
+
+    #[instrument]
+    pub async fn get_all_submissions(&self) -> anyhow::Result<Vec<submission::Model>> {
+        Ok(Submission::find()
+            .order_by_asc(submission::Column::Id)
+            .all(&self.0)
+            .await?)
+    }
+
+    /// Returns the submissions of the user, newest first
+    #[instrument]
+    pub async fn get_user_submissions(
+        &self,
+        user_id: &str,
+    ) -> anyhow::Result<Vec<submission::Model>> {
+        Ok(Submission::find()
+            .filter(submission::Column::UserId.eq(user_id))
+            .order_by_desc(submission::Column::Datetime)
+            .all(&self.0)
+            .await?)
+    }
+
+    #[instrument]
+    pub async fn get_submission(&self, id: i32) -> anyhow::Result<Option<submission::Model>> {
+        Ok(Submission::find_by_id(id).one(&self.0).await?)
+    }
+
     #[instrument(skip(round_result))]
     pub async fn add_round_result(
         &self,
         round_result: &RoundResult,
         player_strategies: BTreeMap<String, i32>,
+        final_tournament: bool,
     ) -> anyhow::Result<i32> {
-        let rr = round_result::Model {
-            id: 0,
-            result: serde_json::to_string(round_result)?,
-            participants: serde_json::to_string(&player_strategies)?,
-            datetime: DateTimeUtc::from(SystemTime::now()),
+        let submission = |name: &str| {
+            player_strategies
+                .get(name)
+                .copied()
+                .ok_or_else(|| anyhow!("{name} played without a submission"))
         };
 
-        let mut am = rr.into_active_model();
-        am.id = ActiveValue::NotSet;
+        let txn = self.0.begin().await?;
 
-        let id = round_result::Entity::insert(am)
-            .exec(&self.0)
+        let round_id = round::Entity::insert(round::ActiveModel {
+            id: ActiveValue::NotSet,
+            datetime: ActiveValue::Set(DateTimeUtc::from(SystemTime::now())),
+            final_tournament: ActiveValue::Set(final_tournament),
+        })
+        .exec(&txn)
+        .await?
+        .last_insert_id;
+
+        for m in &round_result.0 {
+            let match_id = match_result::Entity::insert(match_result::ActiveModel {
+                id: ActiveValue::NotSet,
+                round_id: ActiveValue::Set(round_id),
+                moves: ActiveValue::Set(m.moves as i32),
+            })
+            .exec(&txn)
             .await?
             .last_insert_id;
 
-        Ok(id)
+            let mut results = Vec::new();
+            for (player, opponent, first) in [
+                (&m.player1, &m.player2, true),
+                (&m.player2, &m.player1, false),
+            ] {
+                let (score, error) = match &player.outcome {
+                    Ok(score) => (Some(*score), None),
+                    Err(e) => (None, Some(e.clone())),
+                };
+                results.push(player_result::ActiveModel {
+                    id: ActiveValue::NotSet,
+                    match_id: ActiveValue::Set(match_id),
+                    round_id: ActiveValue::Set(round_id),
+                    user_id: ActiveValue::Set(player.player_name.clone()),
+                    submission_id: ActiveValue::Set(submission(&player.player_name)?),
+                    opponent_user_id: ActiveValue::Set(opponent.player_name.clone()),
+                    opponent_submission_id: ActiveValue::Set(submission(&opponent.player_name)?),
+                    first: ActiveValue::Set(first),
+                    score: ActiveValue::Set(score),
+                    error: ActiveValue::Set(error),
+                    moves: ActiveValue::Set(serde_json::to_string(&player.moves)?),
+                });
+            }
+            player_result::Entity::insert_many(results)
+                .exec(&txn)
+                .await?;
+        }
+
+        txn.commit().await?;
+
+        Ok(round_id)
+    }
+
+    /// Puts the stored matches of the rounds back together, keeping the order of the rounds
+    #[instrument(skip(rounds))]
+    async fn load_rounds(
+        &self,
+        rounds: Vec<round::Model>,
+    ) -> anyhow::Result<Vec<(round::Model, BTreeMap<String, i32>, RoundResult)>> {
+        let ids: Vec<_> = rounds.iter().map(|r| r.id).collect();
+
+        let moves: HashMap<_, _> = match_result::Entity::find()
+            .filter(match_result::Column::RoundId.is_in(ids.clone()))
+            .all(&self.0)
+            .await?
+            .into_iter()
+            .map(|m| (m.id, m.moves))
+            .collect();
+
+        let results = player_result::Entity::find()
+            .filter(player_result::Column::RoundId.is_in(ids))
+            .order_by_asc(player_result::Column::MatchId)
+            .all(&self.0)
+            .await?;
+
+        let mut by_round: HashMap<_, (BTreeMap<_, _>, BTreeMap<_, Vec<_>>)> = HashMap::new();
+        for r in results {
+            let (participants, matches) = by_round.entry(r.round_id).or_default();
+            participants.insert(r.user_id.clone(), r.submission_id);
+            matches.entry(r.match_id).or_default().push(r);
+        }
+
+        rounds
+            .into_iter()
+            .map(|round| {
+                let (participants, matches) = by_round.remove(&round.id).unwrap_or_default();
+                let matches = matches
+                    .into_iter()
+                    .map(|(match_id, mut players)| {
+                        players.sort_by_key(|p| !p.first);
+                        let (player1, player2) = match players.as_slice() {
+                            [p1, p2] => (stored_player_result(p1)?, stored_player_result(p2)?),
+                            _ => return Err(anyhow!("Match {match_id} is broken")),
+                        };
+                        Ok(MatchResult {
+                            moves: moves.get(&match_id).copied().unwrap_or_default() as u32,
+                            player1,
+                            player2,
+                        })
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+
+                Ok((round, participants, RoundResult(matches)))
+            })
+            .collect()
     }
 
+    /// Returns the last rounds along with the submissions of their participants, newest first
     #[instrument]
-    pub async fn get_last_rounds_results(&self) -> anyhow::Result<(Vec<RoundResult>, DateTimeUtc)> {
-        info!("Getting last rounds results");
-        let r = round_result::Entity::find()
-            .order_by_desc(round_result::Column::Datetime)
-            .limit(20)
+    pub async fn get_last_rounds_with_participants(
+        &self,
+        limit: u64,
+    ) -> anyhow::Result<Vec<(BTreeMap<String, i32>, RoundResult)>> {
+        let rounds = round::Entity::find()
+            .order_by_desc(round::Column::Datetime)
+            .limit(limit)
+            .all(&self.0)
+            .await?;
+
+        Ok(self
+            .load_rounds(rounds)
+            .await?
+            .into_iter()
+            .map(|(_, participants, result)| (participants, result))
+            .collect())
+    }
+
+    /// Stores the scoreboard computed after the round, as (user id, rank, score) positions
+    #[instrument(skip(positions))]
+    pub async fn add_scoreboard_snapshot(
+        &self,
+        round_id: i32,
+        datetime: DateTimeUtc,
+        positions: &[(String, i32, f64)],
+    ) -> anyhow::Result<()> {
+        if positions.is_empty() {
+            return Ok(());
+        }
+
+        let entries =
+            positions
+                .iter()
+                .map(|(user_id, rank, score)| scoreboard_entry::ActiveModel {
+                    id: ActiveValue::NotSet,
+                    round_id: ActiveValue::Set(round_id),
+                    datetime: ActiveValue::Set(datetime),
+                    user_id: ActiveValue::Set(user_id.clone()),
+                    rank: ActiveValue::Set(*rank),
+                    score: ActiveValue::Set(*score),
+                });
+
+        scoreboard_entry::Entity::insert_many(entries)
+            .exec(&self.0)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the positions of the user on every stored scoreboard, oldest first
+    #[instrument]
+    pub async fn get_scoreboard_history(
+        &self,
+        user_id: &str,
+    ) -> anyhow::Result<Vec<scoreboard_entry::Model>> {
+        Ok(scoreboard_entry::Entity::find()
+            .filter(scoreboard_entry::Column::UserId.eq(user_id))
+            .order_by_asc(scoreboard_entry::Column::Datetime)
+            .all(&self.0)
+            .await?)
+    }
+
+    /// Returns the positions of the last scoreboard stored not later than `datetime`, top first
+    #[instrument]
+    pub async fn get_scoreboard_at(
+        &self,
+        datetime: DateTimeUtc,
+    ) -> anyhow::Result<Vec<scoreboard_entry::Model>> {
+        let last = scoreboard_entry::Entity::find()
+            .filter(scoreboard_entry::Column::Datetime.lte(datetime))
+            .order_by_desc(scoreboard_entry::Column::Datetime)
+            .one(&self.0)
+            .await?;
+
+        let last = match last {
+            Some(l) => l,
+            None => return Ok(Vec::new()),
+        };
+
+        Ok(scoreboard_entry::Entity::find()
+            .filter(scoreboard_entry::Column::RoundId.eq(last.round_id))
+            .order_by_asc(scoreboard_entry::Column::Rank)
+            .order_by_asc(scoreboard_entry::Column::UserId)
+            .all(&self.0)
+            .await?)
+    }
+
+    /// Returns the matches between the two players with the time of their rounds, oldest first.
+    ///
+    /// Each match is given as the results of the player and of the opponent
+    #[instrument]
+    pub async fn get_head_to_head_results(
+        &self,
+        player: &str,
+        opponent: &str,
+    ) -> anyhow::Result<Vec<(DateTimeUtc, player_result::Model, player_result::Model)>> {
+        let results = player_result::Entity::find()
+            .filter(
+                Condition::any()
+                    .add(
+                        player_result::Column::UserId
+                            .eq(player)
+                            .and(player_result::Column::OpponentUserId.eq(opponent)),
+                    )
+                    .add(
+                        player_result::Column::UserId
+                            .eq(opponent)
+                            .and(player_result::Column::OpponentUserId.eq(player)),
+                    ),
+            )
+            .order_by_asc(player_result::Column::MatchId)
+            .all(&self.0)
+            .await?;
+
+        let datetimes: HashMap<_, _> = round::Entity::find()
+            .filter(round::Column::Id.is_in(results.iter().map(|r| r.round_id).unique()))
+            .all(&self.0)
+            .await?
+            .into_iter()
+            .map(|r| (r.id, r.datetime))
+            .collect();
+
+        let mut res: Vec<_> = results
+            .into_iter()
+            .group_by(|r| r.match_id)
+            .into_iter()
+            .map(|(match_id, players)| {
+                let (mut p, mut o) = (None, None);
+                for r in players {
+                    if r.user_id == player {
+                        p = Some(r);
+                    } else {
+                        o = Some(r);
+                    }
+                }
+                let broken = || anyhow!("Match {match_id} is broken");
+                let (p, o) = (p.ok_or_else(broken)?, o.ok_or_else(broken)?);
+                let datetime = *datetimes.get(&p.round_id).ok_or_else(broken)?;
+                Ok((datetime, p, o))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        res.sort_by_key(|(datetime, _, _)| *datetime);
+
+        Ok(res)
+    }
+
+    /// Returns the last rounds along with their participants, newest first
+    #[instrument]
+    pub async fn get_last_rounds_list(
+        &self,
+        limit: u64,
+    ) -> anyhow::Result<Vec<(round::Model, BTreeMap<String, i32>)>> {
+        let rounds = round::Entity::find()
+            .order_by_desc(round::Column::Datetime)
+            .limit(limit)
             .all(&self.0)
             .await?;
 
-        if r.is_empty() {
-            return Err(anyhow!("No round results available"));
+        let participants = player_result::Entity::find()
+            .select_only()
+            .column(player_result::Column::RoundId)
+            .column(player_result::Column::UserId)
+            .column(player_result::Column::SubmissionId)
+            .filter(player_result::Column::RoundId.is_in(rounds.iter().map(|r| r.id)))
+            .group_by(player_result::Column::RoundId)
+            .group_by(player_result::Column::UserId)
+            .group_by(player_result::Column::SubmissionId)
+            .into_model::<Participant>()
+            .all(&self.0)
+            .await?;
+
+        let mut by_round: HashMap<_, BTreeMap<_, _>> = HashMap::new();
+        for p in participants {
+            by_round
+                .entry(p.round_id)
+                .or_default()
+                .insert(p.user_id, p.submission_id);
         }
 
-        let last_time = r.iter().map(|f| f.datetime).max().unwrap();
+        Ok(rounds
+            .into_iter()
+            .map(|r| {
+                let participants = by_round.remove(&r.id).unwrap_or_default();
+                (r, participants)
+            })
+            .collect())
+    }
+
+    /// Returns the match between the two players in the round, as (moves, player result, opponent result)
+    #[instrument]
+    pub async fn get_match(
+        &self,
+        round_id: i32,
+        player: &str,
+        opponent: &str,
+    ) -> anyhow::Result<Option<(u32, PlayerResult, PlayerResult)>> {
+        let own = player_result::Entity::find()
+            .filter(
+                player_result::Column::RoundId
+                    .eq(round_id)
+                    .and(player_result::Column::UserId.eq(player))
+                    .and(player_result::Column::OpponentUserId.eq(opponent)),
+            )
+            .one(&self.0)
+            .await?;
+        let own = match own {
+            Some(r) => r,
+            None => return Ok(None),
+        };
+
+        let other = player_result::Entity::find()
+            .filter(
+                player_result::Column::MatchId
+                    .eq(own.match_id)
+                    .and(player_result::Column::Id.ne(own.id)),
+            )
+            .one(&self.0)
+            .await?
+            .ok_or_else(|| anyhow!("Match {} is broken", own.match_id))?;
+
+        let moves = match_result::Entity::find_by_id(own.match_id)
+            .one(&self.0)
+            .await?
+            .map_or(0, |m| m.moves);
+
+        Ok(Some((
+            moves as u32,
+            stored_player_result(&own)?,
+            stored_player_result(&other)?,
+        )))
+    }
+
+    #[instrument]
+    pub async fn get_round_result(
+        &self,
+        id: i32,
+    ) -> anyhow::Result<Option<(RoundResult, DateTimeUtc)>> {
+        let round = match round::Entity::find_by_id(id).one(&self.0).await? {
+            Some(r) => r,
+            None => return Ok(None),
+        };
+
+        Ok(self
+            .load_rounds(vec![round])
+            .await?
+            .pop()
+            .map(|(round, _, result)| (result, round.datetime)))
+    }
+
+    /// Returns the regular rounds in the scoring window, newest first
+    #[instrument]
+    async fn get_window_rounds(&self, window: &ScoringWindow) -> anyhow::Result<Vec<round::Model>> {
+        let q = round::Entity::find()
+            .filter(round::Column::FinalTournament.eq(false))
+            .order_by_desc(round::Column::Datetime);
+
+        Ok(match window {
+            ScoringWindow::Rounds { count } => q.limit(*count).all(&self.0).await?,
+            ScoringWindow::Time { minutes } => {
+                let since = DateTimeUtc::from(SystemTime::now())
+                    - chrono::Duration::minutes(*minutes as i64);
+                q.filter(round::Column::Datetime.gte(since))
+                    .all(&self.0)
+                    .await?
+            }
+        })
+    }
+
+    /// Returns the regular rounds in the scoring window along with the submissions of their
+    /// participants, newest first, and the time of the last one
+    #[instrument]
+    pub async fn get_last_rounds_results(
+        &self,
+        window: &ScoringWindow,
+    ) -> anyhow::Result<(Vec<(BTreeMap<String, i32>, RoundResult)>, DateTimeUtc)> {
+        info!("Getting last rounds results");
+        let r = self.get_window_rounds(window).await?;
+
+        self.rounds_results(r).await
+    }
 
-        let round_results: serde_json::Result<Vec<RoundResult>> =
-            r.iter().map(|f| serde_json::from_str(&f.result)).collect();
-        let round_results = round_results?;
+    /// Returns the rounds of the final tournament played so far, as `get_last_rounds_results` does
+    #[instrument]
+    pub async fn get_final_rounds_results(
+        &self,
+    ) -> anyhow::Result<(Vec<(BTreeMap<String, i32>, RoundResult)>, DateTimeUtc)> {
+        let r = round::Entity::find()
+            .filter(round::Column::FinalTournament.eq(true))
+            .order_by_desc(round::Column::Datetime)
+            .all(&self.0)
+            .await?;
+
+        self.rounds_results(r).await
+    }
+
+    #[instrument]
+    pub async fn count_final_rounds(&self) -> anyhow::Result<u64> {
+        Ok(round::Entity::find()
+            .filter(round::Column::FinalTournament.eq(true))
+            .count(&self.0)
+            .await? as u64)
+    }
+
+    /// The time is the epoch if there are no rounds
+    async fn rounds_results(
+        &self,
+        r: Vec<round::Model>,
+    ) -> anyhow::Result<(Vec<(BTreeMap<String, i32>, RoundResult)>, DateTimeUtc)> {
+        let last_time = r
+            .iter()
+            .map(|f| f.datetime)
+            .max()
+            .unwrap_or_else(|| DateTimeUtc::from(SystemTime::UNIX_EPOCH));
+
+        let round_results = self
+            .load_rounds(r)
+            .await?
+            .into_iter()
+            .map(|(_, participants, result)| (participants, result))
+            .collect();
 
         Ok((round_results, last_time))
     }
@@ -0,0 +1,452 @@
+use crate::api::rounds::{scoreboard_ranks, Scoreboard};
+use crate::api::submissions::ValidationReport;
+use entity::submission;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use tracing::warn;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BaselineThreshold {
+    /// Name of the player in the standings to compare with, usually a house strategy
+    pub baseline: String,
+    pub points: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PercentileBand {
+    /// The share of the ranked users, in percent, the user should be at or above
+    pub min_percentile: f64,
+    pub points: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DummyBonus {
+    /// One of the dummy strategies the submissions are validated against
+    pub dummy: String,
+    pub points: f64,
+}
+
+/// How the points of a user are made up, all the parts are summed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Rubric {
+    /// Points for having a valid submission, nothing else counts without it
+    pub valid_points: f64,
+    /// Points for scoring at least as much as the baseline in the standings, every reached threshold counts
+    pub baseline_thresholds: Vec<BaselineThreshold>,
+    /// Points for the rank, only the highest band reached counts
+    pub percentile_bands: Vec<PercentileBand>,
+    /// Points for outscoring a dummy strategy in the validation matches of the graded submission
+    pub dummy_bonuses: Vec<DummyBonus>,
+    /// The grade the gradebook column is out of, the grades are capped at it
+    pub max_points: f64,
+}
+
+impl Default for Rubric {
+    fn default() -> Self {
+        Rubric {
+            valid_points: 100.0,
+            baseline_thresholds: Vec::new(),
+            percentile_bands: Vec::new(),
+            dummy_bonuses: Vec::new(),
+            max_points: 100.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Grade {
+    pub user_id: String,
+    /// The submission that played in the rounds of the standings, the latest valid one (or the latest one if
+    /// none is valid) for the users who did not play
+    pub submission_id: i32,
+    pub valid: bool,
+    pub rank: Option<i32>,
+    pub percentile: Option<f64>,
+    pub score: Option<f64>,
+    /// Points before the late penalty
+    pub points: f64,
+    /// The multiplier applied for the late graded submission
+    pub late_penalty: Option<f64>,
+    /// Points after the late penalty, capped at the maximum
+    pub grade: f64,
+    /// How the points were made up, for the feedback column
+    pub breakdown: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Grades {
+    /// The grades are computed from the final tournament, not from the live scoreboard
+    pub final_standings: bool,
+    pub max_points: f64,
+    pub grades: Vec<Grade>,
+}
+
+/// Total scores of the submission and of the dummy in their validation matches, by dummy name
+fn validation_scores(report: &ValidationReport) -> BTreeMap<&str, (f64, f64)> {
+    let mut res = BTreeMap::new();
+    for (player, opponent) in report.matches.iter().flatten() {
+        let entry = res
+            .entry(opponent.player_name.as_str())
+            .or_insert((0.0, 0.0));
+        entry.0 += player.outcome.as_ref().copied().unwrap_or(0.0);
+        entry.1 += opponent.outcome.as_ref().copied().unwrap_or(0.0);
+    }
+    res
+}
+
+fn grade_user(
+    rubric: &Rubric,
+    user_id: &str,
+    submission: &submission::Model,
+    standings: &HashMap<&str, (i32, f64)>,
+    scores: &HashMap<&str, f64>,
+) -> Grade {
+    let standing = standings.get(user_id).copied();
+    let mut points = 0.0;
+    let mut breakdown = Vec::new();
+    let mut add = |p: f64, what: String| {
+        points += p;
+        breakdown.push(format!("{what}: {p}"));
+    };
+
+    let ranked_count = standings.len() as f64;
+    let percentile =
+        standing.map(|(rank, _)| 100.0 * (ranked_count - rank as f64 + 1.0) / ranked_count);
+
+    if submission.is_valid() {
+        add(rubric.valid_points, "valid submission".to_string());
+
+        if let Some((_, score)) = standing {
+            for t in &rubric.baseline_thresholds {
+                match scores.get(t.baseline.as_str()) {
+                    Some(&baseline) if score >= baseline => {
+                        add(t.points, format!("at least {}", t.baseline))
+                    }
+                    Some(_) => {}
+                    None => warn!("The baseline {} is not in the standings", t.baseline),
+                }
+            }
+        }
+
+        if let Some(percentile) = percentile {
+            if let Some(band) = rubric
+                .percentile_bands
+                .iter()
+                .filter(|b| percentile >= b.min_percentile)
+                .max_by(|a, b| a.min_percentile.total_cmp(&b.min_percentile))
+            {
+                add(band.points, format!("top {}%", 100.0 - band.min_percentile));
+            }
+        }
+
+        let report: Option<ValidationReport> = submission
+            .report
+            .as_deref()
+            .and_then(|r| serde_json::from_str(r).ok());
+        if let Some(report) = report {
+            let validation = validation_scores(&report);
+            for b in &rubric.dummy_bonuses {
+                if validation
+                    .get(b.dummy.as_str())
+                    .is_some_and(|(own, dummy)| own > dummy)
+                {
+                    add(b.points, format!("beats {}", b.dummy));
+                }
+            }
+        }
+    }
+
+    let mut grade = points;
+    if let Some(factor) = submission.late_penalty {
+        grade *= factor;
+        breakdown.push(format!("late: x{factor}"));
+    }
+
+    Grade {
+        user_id: user_id.to_string(),
+        submission_id: submission.id,
+        valid: submission.is_valid(),
+        rank: standing.map(|(rank, _)| rank),
+        percentile,
+        score: standing.map(|(_, score)| score),
+        points,
+        late_penalty: submission.late_penalty,
+        grade: grade.min(rubric.max_points),
+        breakdown: breakdown.join("; "),
+    }
+}
+
+/// Grades every user who made a submission by the standings and the submission they played them with
+///
+/// `played` is the submission of each user in the latest of the rounds the standings are computed over, the
+/// validity, the bonuses and the late penalty are all taken from it.
+pub fn compute_grades(
+    rubric: &Rubric,
+    standings: &Scoreboard,
+    final_standings: bool,
+    submissions: &[submission::Model],
+    played: &HashMap<String, i32>,
+) -> Grades {
+    // the house strategies are only compared with, they do not take the ranks
+    let scores: HashMap<&str, f64> = standings
+        .positions
+        .iter()
+        .map(|(name, score, _)| (name.as_str(), *score))
+        .collect();
+    let ranks = scoreboard_ranks(&standings.without(&standings.house));
+    let standings: HashMap<&str, (i32, f64)> = ranks
+        .iter()
+        .map(|(n, rank, s)| (n.as_str(), (*rank, *s)))
+        .collect();
+
+    let grades = submissions
+        .iter()
+        .into_group_map_by(|s| s.user_id.as_str())
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(user_id, submissions)| {
+            let graded = match played
+                .get(user_id)
+                .and_then(|id| submissions.iter().find(|s| s.id == *id))
+            {
+                Some(graded) => graded,
+                // the latest valid one, falling back to the latest invalid one
+                None => submissions
+                    .iter()
+                    .max_by_key(|s| (s.is_valid(), s.datetime))
+                    .unwrap(),
+            };
+            grade_user(rubric, user_id, graded, &standings, &scores)
+        })
+        .collect();
+
+    Grades {
+        final_standings,
+        max_points: rubric.max_points,
+        grades,
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// The grades as CSV a gradebook can import, one row per user
+pub fn grades_csv(grades: &Grades) -> String {
+    let mut res = String::new();
+    writeln!(
+        &mut res,
+        "user_id,grade,max_points,points,late_penalty,rank,percentile,score,submission_id,valid,feedback"
+    )
+    .unwrap();
+
+    let opt = |v: Option<String>| v.unwrap_or_default();
+    for g in &grades.grades {
+        writeln!(
+            &mut res,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            csv_field(&g.user_id),
+            g.grade,
+            grades.max_points,
+            g.points,
+            opt(g.late_penalty.map(|p| p.to_string())),
+            opt(g.rank.map(|r| r.to_string())),
+            opt(g.percentile.map(|p| format!("{p:.1}"))),
+            opt(g.score.map(|s| format!("{s:.3}"))),
+            g.submission_id,
+            g.valid,
+            csv_field(&g.breakdown),
+        )
+        .unwrap();
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn submission(id: i32, user_id: &str, age_hours: i64, valid: bool) -> submission::Model {
+        submission::Model {
+            id,
+            user_id: user_id.to_string(),
+            code: String::new(),
+            datetime: Utc::now() - Duration::hours(age_hours),
+            valid,
+            files: None,
+            jdk_version: 17,
+            report: None,
+            late_penalty: None,
+            ranked: true,
+            invalidated: false,
+        }
+    }
+
+    fn rubric() -> Rubric {
+        Rubric {
+            valid_points: 50.0,
+            baseline_thresholds: vec![
+                BaselineThreshold {
+                    baseline: "house:random".to_string(),
+                    points: 20.0,
+                },
+                BaselineThreshold {
+                    baseline: "house:greedy".to_string(),
+                    points: 30.0,
+                },
+            ],
+            percentile_bands: vec![
+                PercentileBand {
+                    min_percentile: 50.0,
+                    points: 10.0,
+                },
+                PercentileBand {
+                    min_percentile: 90.0,
+                    points: 25.0,
+                },
+            ],
+            dummy_bonuses: Vec::new(),
+            max_points: 100.0,
+        }
+    }
+
+    fn standings() -> Scoreboard {
+        let positions = [
+            ("alice", 10.0),
+            ("house:greedy", 8.0),
+            ("bob", 5.0),
+            ("house:random", 4.0),
+            ("carol", 2.0),
+        ];
+        Scoreboard {
+            positions: positions
+                .iter()
+                .map(|(name, score)| (name.to_string(), *score, None))
+                .collect(),
+            house: ["house:greedy".to_string(), "house:random".to_string()].into(),
+            ..Scoreboard::default()
+        }
+    }
+
+    fn grades(submissions: &[submission::Model]) -> HashMap<String, Grade> {
+        let played = submissions
+            .iter()
+            .map(|s| (s.user_id.clone(), s.id))
+            .collect();
+        compute_grades(&rubric(), &standings(), true, submissions, &played)
+            .grades
+            .into_iter()
+            .map(|g| (g.user_id.clone(), g))
+            .collect()
+    }
+
+    #[test]
+    fn thresholds_and_the_highest_band() {
+        let grades = grades(&[
+            submission(1, "alice", 1, true),
+            submission(2, "bob", 1, true),
+            submission(3, "carol", 1, true),
+        ]);
+
+        // both baselines and only the top band of the two reached
+        let alice = &grades["alice"];
+        assert_eq!((alice.rank, alice.percentile), (Some(1), Some(100.0)));
+        assert_eq!(alice.points, 125.0);
+        assert_eq!(alice.grade, 100.0);
+        assert_eq!(
+            alice.breakdown,
+            "valid submission: 50; at least house:random: 20; at least house:greedy: 30; top 10%: 25"
+        );
+
+        let bob = &grades["bob"];
+        assert_eq!(bob.rank, Some(2));
+        assert_eq!(bob.points, 80.0);
+
+        let carol = &grades["carol"];
+        assert_eq!(carol.rank, Some(3));
+        assert_eq!(carol.points, 50.0);
+        assert_eq!(carol.breakdown, "valid submission: 50");
+    }
+
+    #[test]
+    fn late_penalty_before_the_cap() {
+        let late = |id, user_id, factor| submission::Model {
+            late_penalty: Some(factor),
+            ..submission(id, user_id, 1, true)
+        };
+        let grades = grades(&[late(1, "alice", 0.9), late(2, "bob", 0.5)]);
+
+        let alice = &grades["alice"];
+        assert_eq!((alice.points, alice.grade), (125.0, 100.0));
+        assert_eq!(alice.late_penalty, Some(0.9));
+        assert!(alice.breakdown.ends_with("; late: x0.9"));
+
+        let bob = &grades["bob"];
+        assert_eq!((bob.points, bob.grade), (80.0, 40.0));
+    }
+
+    #[test]
+    fn graded_submission() {
+        let submissions = [
+            // alice played with the older one
+            submission(1, "alice", 3, true),
+            submission(2, "alice", 1, true),
+            // dave did not play, the newer invalid one does not count
+            submission(3, "dave", 3, false),
+            submission(4, "dave", 2, true),
+            submission(5, "dave", 1, false),
+            submission(6, "erin", 1, false),
+        ];
+        let played = [("alice".to_string(), 1)].into();
+
+        let grades = compute_grades(&rubric(), &standings(), true, &submissions, &played).grades;
+        let graded: Vec<_> = grades
+            .iter()
+            .map(|g| (g.user_id.as_str(), g.submission_id, g.valid))
+            .collect();
+        assert_eq!(
+            graded,
+            [("alice", 1, true), ("dave", 4, true), ("erin", 6, false)]
+        );
+        assert_eq!(grades[1].rank, None);
+        assert_eq!(grades[1].points, 50.0);
+        assert_eq!(grades[2].grade, 0.0);
+    }
+
+    #[test]
+    fn csv_quoting() {
+        assert_eq!(csv_field("plain text"), "plain text");
+        assert_eq!(csv_field("a, b"), "\"a, b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+
+        let grades = Grades {
+            final_standings: true,
+            max_points: 100.0,
+            grades: vec![Grade {
+                user_id: "alice".to_string(),
+                submission_id: 7,
+                valid: true,
+                rank: Some(1),
+                percentile: Some(100.0),
+                score: Some(10.0),
+                points: 75.0,
+                late_penalty: None,
+                grade: 75.0,
+                breakdown: "beats \"greedy\", twice".to_string(),
+            }],
+        };
+        assert_eq!(
+            grades_csv(&grades),
+            "user_id,grade,max_points,points,late_penalty,rank,percentile,score,submission_id,valid,feedback\n\
+            alice,75,100,75,,1,100.0,10.000,7,true,\"beats \"\"greedy\"\", twice\"\n"
+        );
+    }
+}
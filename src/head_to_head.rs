@@ -0,0 +1,236 @@
+use entity::player_result;
+use entity::sea_orm::prelude::DateTimeUtc;
+use serde::{Deserialize, Serialize};
+
+/// Scores closer than that are a draw
+const DRAW_EPSILON: f64 = 1e-9;
+/// Number of the meetings the trend is averaged over
+const TREND_WINDOW: usize = 10;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Win,
+    Loss,
+    Draw,
+    /// One of the players failed, the scores are not comparable
+    Error,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Record {
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+    pub errors: usize,
+}
+
+impl Record {
+    fn add(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Win => self.wins += 1,
+            Outcome::Loss => self.losses += 1,
+            Outcome::Draw => self.draws += 1,
+            Outcome::Error => self.errors += 1,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Distribution {
+    pub count: usize,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub quartiles: [f64; 3],
+    pub max: f64,
+}
+
+impl Distribution {
+    fn from_scores(scores: &[f64]) -> Option<Self> {
+        if scores.is_empty() {
+            return None;
+        }
+
+        let mut sorted = scores.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let count = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / count as f64;
+        let variance = if count > 1 {
+            sorted.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (count - 1) as f64
+        } else {
+            0.0
+        };
+        // linear interpolation between the closest ranks
+        let quantile = |q: f64| {
+            let rank = q * (count - 1) as f64;
+            let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+            sorted[lo] + (sorted[hi] - sorted[lo]) * (rank - lo as f64)
+        };
+
+        Some(Distribution {
+            count,
+            mean,
+            std_dev: variance.sqrt(),
+            min: sorted[0],
+            quartiles: [quantile(0.25), quantile(0.5), quantile(0.75)],
+            max: sorted[count - 1],
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Meeting {
+    pub datetime: DateTimeUtc,
+    /// `0` for the house strategies
+    pub player_submission: i32,
+    pub opponent_submission: i32,
+    pub player_result: Result<f64, String>,
+    pub opponent_result: Result<f64, String>,
+    pub outcome: Outcome,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrendPoint {
+    pub datetime: DateTimeUtc,
+    /// Moving average of the player score minus the opponent score over the last meetings without errors
+    pub mean_margin: f64,
+}
+
+/// Results of one pair of submission versions
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VersionRecord {
+    pub player_submission: i32,
+    pub opponent_submission: i32,
+    pub first: DateTimeUtc,
+    pub last: DateTimeUtc,
+    pub record: Record,
+    pub player_scores: Option<Distribution>,
+    pub opponent_scores: Option<Distribution>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HeadToHead {
+    pub player: String,
+    pub opponent: String,
+    pub record: Record,
+    pub player_scores: Option<Distribution>,
+    pub opponent_scores: Option<Distribution>,
+    /// Every meeting, oldest first
+    pub meetings: Vec<Meeting>,
+    pub trend: Vec<TrendPoint>,
+    /// In the order the versions first met
+    pub versions: Vec<VersionRecord>,
+}
+
+fn outcome(player: &Result<f64, String>, opponent: &Result<f64, String>) -> Outcome {
+    match (player, opponent) {
+        (Ok(p), Ok(o)) if (p - o).abs() <= DRAW_EPSILON => Outcome::Draw,
+        (Ok(p), Ok(o)) if p > o => Outcome::Win,
+        (Ok(_), Ok(_)) => Outcome::Loss,
+        _ => Outcome::Error,
+    }
+}
+
+fn scores<'a>(results: impl Iterator<Item = &'a Result<f64, String>>) -> Vec<f64> {
+    results.filter_map(|r| r.as_ref().ok().copied()).collect()
+}
+
+/// Aggregates the matches between the two players,
+/// given with the time of their rounds as (player result, opponent result), oldest first
+pub fn compute_head_to_head(
+    results: &[(DateTimeUtc, player_result::Model, player_result::Model)],
+    player: &str,
+    opponent: &str,
+) -> HeadToHead {
+    let meetings: Vec<_> = results
+        .iter()
+        .map(|(datetime, p, o)| {
+            let (player_result, opponent_result) = (p.outcome(), o.outcome());
+            Meeting {
+                datetime: *datetime,
+                player_submission: p.submission_id,
+                opponent_submission: o.submission_id,
+                outcome: outcome(&player_result, &opponent_result),
+                player_result,
+                opponent_result,
+            }
+        })
+        .collect();
+
+    let mut record = Record::default();
+    let mut versions: Vec<(VersionRecord, Vec<&Meeting>)> = Vec::new();
+    for m in &meetings {
+        record.add(m.outcome);
+
+        let key = (m.player_submission, m.opponent_submission);
+        let version = match versions
+            .iter_mut()
+            .find(|(v, _)| (v.player_submission, v.opponent_submission) == key)
+        {
+            Some(v) => v,
+            None => {
+                versions.push((
+                    VersionRecord {
+                        player_submission: key.0,
+                        opponent_submission: key.1,
+                        first: m.datetime,
+                        last: m.datetime,
+                        record: Record::default(),
+                        player_scores: None,
+                        opponent_scores: None,
+                    },
+                    Vec::new(),
+                ));
+                versions.last_mut().unwrap()
+            }
+        };
+        version.0.last = m.datetime;
+        version.0.record.add(m.outcome);
+        version.1.push(m);
+    }
+
+    let versions = versions
+        .into_iter()
+        .map(|(mut v, meetings)| {
+            v.player_scores =
+                Distribution::from_scores(&scores(meetings.iter().map(|m| &m.player_result)));
+            v.opponent_scores =
+                Distribution::from_scores(&scores(meetings.iter().map(|m| &m.opponent_result)));
+            v
+        })
+        .collect();
+
+    let margins: Vec<_> = meetings
+        .iter()
+        .filter_map(|m| match (&m.player_result, &m.opponent_result) {
+            (Ok(p), Ok(o)) => Some((m.datetime, p - o)),
+            _ => None,
+        })
+        .collect();
+    let trend = (0..margins.len())
+        .map(|i| {
+            let window = &margins[(i + 1).saturating_sub(TREND_WINDOW)..=i];
+            TrendPoint {
+                datetime: margins[i].0,
+                mean_margin: window.iter().map(|(_, m)| m).sum::<f64>() / window.len() as f64,
+            }
+        })
+        .collect();
+
+    HeadToHead {
+        player: player.to_string(),
+        opponent: opponent.to_string(),
+        record,
+        player_scores: Distribution::from_scores(&scores(
+            meetings.iter().map(|m| &m.player_result),
+        )),
+        opponent_scores: Distribution::from_scores(&scores(
+            meetings.iter().map(|m| &m.opponent_result),
+        )),
+        meetings,
+        trend,
+        versions,
+    }
+}
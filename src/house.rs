@@ -0,0 +1,63 @@
+//! The house strategies: the configured dummy strategies and the ones added by the admins.
+//! They play in every round as the reference lines for the users, but are never graded.
+
+use crate::contest::ContestConfig;
+use crate::Database;
+use execution::jdk::JdkVersion;
+use execution::matchmaker::{single_file_sources, SourceFile, DUMMY_STRATS};
+use std::collections::BTreeSet;
+
+/// Prefix of the names the house strategies play under, so that they never clash with the user ids
+pub const HOUSE_PREFIX: &str = "house:";
+
+pub fn is_house(player: &str) -> bool {
+    player.starts_with(HOUSE_PREFIX)
+}
+
+pub struct HousePlayer {
+    /// Prefixed with `HOUSE_PREFIX`
+    pub name: String,
+    pub sources: Vec<SourceFile>,
+    pub jdk: JdkVersion,
+}
+
+/// The house strategies playing in the rounds
+pub async fn house_players(
+    db: &Database,
+    contest: &ContestConfig,
+) -> anyhow::Result<Vec<HousePlayer>> {
+    let dummies = DUMMY_STRATS
+        .iter()
+        .filter(|(name, _)| contest.house_strategies.iter().any(|h| h == name))
+        .map(|(name, code)| HousePlayer {
+            name: format!("{HOUSE_PREFIX}{name}"),
+            sources: single_file_sources(code.to_string()),
+            jdk: contest.default_jdk,
+        });
+
+    let added = db
+        .get_house_strategies()
+        .await?
+        .into_iter()
+        .map(|s| {
+            Ok(HousePlayer {
+                name: format!("{HOUSE_PREFIX}{}", s.name),
+                sources: serde_json::from_str(&s.files)?,
+                jdk: JdkVersion(s.jdk_version as u32),
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(dummies.chain(added).collect())
+}
+
+/// The names the hidden house strategies play under
+pub async fn hidden_house_players(db: &Database) -> anyhow::Result<BTreeSet<String>> {
+    Ok(db
+        .get_house_strategies()
+        .await?
+        .into_iter()
+        .filter(|s| s.hidden)
+        .map(|s| format!("{HOUSE_PREFIX}{}", s.name))
+        .collect())
+}
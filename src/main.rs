@@ -1,11 +1,13 @@
-use crate::api::rounds::Scoreboard;
+use crate::background_round_executor::RoundControl;
+use crate::contest::ContestConfig;
 use crate::cors::{CorsMiddleware, Origin};
 use crate::database::Database;
 use crate::reverse_proxy_middleware::ReverseProxyMiddleware;
+use crate::round_cache::RoundCache;
+use crate::validation_queue::ValidationQueue;
 
 use auth::{OpenIdConnectRequestExt, OpenIdConnectRouteExt};
 use execution::compiler::JavaCompiler;
-use execution::matchmaker::RoundResult;
 use execution::runner::Runner;
 use execution::Docker;
 use execution::ExecutionState;
@@ -25,17 +27,30 @@ use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::{EnvFilter, Registry};
 
 mod api;
+mod archive;
 mod background_round_executor;
+mod behaviour;
+mod contest;
 mod cors;
 mod database;
 mod frontend;
+mod grading;
+mod head_to_head;
+mod house;
+mod replay;
 mod reverse_proxy_middleware;
+mod round_cache;
+mod similarity;
+mod validation_queue;
 
 #[derive(Clone, Debug)]
 pub struct State {
     db: Database,
     execution: Arc<ExecutionState>,
-    scoreboard_signal: Arc<Mutable<Arc<(Vec<RoundResult>, Scoreboard)>>>,
+    contest: Arc<ContestConfig>,
+    validation: Arc<ValidationQueue>,
+    round_cache: Arc<Mutable<Arc<RoundCache>>>,
+    rounds: Arc<RoundControl>,
 }
 
 pub fn get_subscriber() -> impl Subscriber + Send + Sync {
@@ -63,12 +78,10 @@ pub fn get_subscriber() -> impl Subscriber + Send + Sync {
     // Create a tracing layer with the configured tracer
     let telemetry = tracing_opentelemetry::layer().with_tracer(jaeger_tracer);
 
-    let registry = Registry::default()
+    Registry::default()
         .with(telemetry)
         .with(filter_layer)
-        .with(fmt_layer);
-
-    registry
+        .with(fmt_layer)
 }
 
 #[async_std::main]
@@ -91,6 +104,16 @@ async fn main() -> tide::Result<()> {
     let frontend_url = env::var("FRONTEND_URL").expect("FRONTEND_URL is not set in .env file");
     let frontend_url = Url::parse(&frontend_url).expect("Couldn't parse the FRONTEND_URL");
 
+    let admins = env::var("ADMIN_USERS").unwrap_or_default();
+    let admins = admins
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect();
+    let admin_group = env::var("ADMIN_GROUP").ok().filter(|g| !g.is_empty());
+    let groups_claim = env::var("ADMIN_GROUPS_CLAIM").unwrap_or_else(|_| "groups".to_string());
+
     let auto_migrate = env::var("AUTO_MIGRATE").expect("AUTO_MIGRATE is not set in .env file");
     let auto_migrate: bool = auto_migrate
         .parse()
@@ -104,25 +127,30 @@ async fn main() -> tide::Result<()> {
             .expect("Migration failed");
     }
 
+    let contest = ContestConfig::load().expect("Cannot load the contest config");
+
     let docker = Docker::new();
 
-    let scoreboard_signal =
-        futures_signals::signal::Mutable::<Arc<(Vec<RoundResult>, Scoreboard)>>::new(
-            Default::default(),
-        );
-    let scoreboard_signal = Arc::new(scoreboard_signal);
+    let db = Database(db);
+    let round_cache = RoundCache::compute(&db, &contest)
+        .await
+        .expect("Cannot compute the scoreboard");
+    let round_cache = Arc::new(Mutable::new(Arc::new(round_cache)));
 
     let mut app = tide::with_state(State {
-        db: Database(db),
+        db,
         execution: Arc::new(ExecutionState {
-            runner: Runner::new(docker.clone())
+            runner: Runner::new(docker.clone(), contest.jdk_images.clone())
                 .await
                 .expect("Cannot create runner"),
-            compiler: JavaCompiler::new(docker)
+            compiler: JavaCompiler::new(docker, contest.jdk_images.clone())
                 .await
                 .expect("Cannot create compiler"),
         }),
-        scoreboard_signal: scoreboard_signal.clone(),
+        contest: Arc::new(contest),
+        validation: Arc::new(ValidationQueue::new()),
+        round_cache: round_cache.clone(),
+        rounds: Arc::new(RoundControl::new()),
     });
 
     let tracer = opentelemetry::global::tracer("tide-server");
@@ -169,6 +197,9 @@ async fn main() -> tide::Result<()> {
             ))
             .unwrap(),
             login_landing_url: frontend_url.join("submission").unwrap(),
+            admins,
+            admin_group,
+            groups_claim,
         })
         .await,
     );
@@ -190,11 +221,94 @@ async fn main() -> tide::Result<()> {
         .authenticated()
         .post(api::submissions::submit);
 
+    api.at("/submissions")
+        .authenticated()
+        .get(api::submissions::get_my_submissions);
+    api.at("/submissions/diff")
+        .authenticated()
+        .get(api::submissions::diff_my_submissions);
+    api.at("/submissions/:id")
+        .authenticated()
+        .get(api::submissions::get_my_submission);
+    api.at("/submissions/:id/activate")
+        .authenticated()
+        .post(api::submissions::activate_submission);
+    api.at("/withdraw")
+        .authenticated()
+        .post(api::submissions::withdraw);
+
+    api.at("/jobs/:id")
+        .authenticated()
+        .get(api::submissions::get_validation_job);
+
+    api.at("/sparring")
+        .authenticated()
+        .post(api::sparring::spar);
+    api.at("/sparring/opponents")
+        .authenticated()
+        .get(api::sparring::get_opponents);
+    api.at("/sparring/partner")
+        .authenticated()
+        .put(api::sparring::set_sparring_partner);
+
+    api.at("/jdks").get(api::submissions::get_jdk_versions);
+
     api.at("/matches")
         .authenticated()
         .get(api::rounds::get_matches);
 
+    api.at("/rounds").get(api::rounds::get_rounds);
+    api.at("/rounds/:id/replay")
+        .authenticated()
+        .get(api::rounds::get_match_replay);
+
+    api.at("/head-to-head")
+        .authenticated()
+        .get(api::rounds::get_head_to_head);
+
     api.at("/scoreboard").get(api::rounds::get_scoreboard);
+    api.at("/contest").get(api::contest::get_status);
+    api.at("/standings").get(api::contest::get_standings);
+    api.at("/scoreboard/history")
+        .get(api::rounds::get_scoreboard_history);
+
+    api.at("/admin/similarity")
+        .admin()
+        .get(api::admin::get_similarity_report);
+    api.at("/admin/grades").admin().get(api::admin::get_grades);
+    api.at("/admin/users").admin().get(api::admin::get_users);
+    api.at("/admin/users/:user_id/ban")
+        .admin()
+        .put(api::admin::set_user_banned);
+    api.at("/admin/submissions")
+        .admin()
+        .get(api::admin::get_submissions);
+    api.at("/admin/submissions/:id")
+        .admin()
+        .get(api::admin::get_submission);
+    api.at("/admin/submissions/:id/invalidate")
+        .admin()
+        .post(api::admin::invalidate_submission);
+    api.at("/admin/submissions/:id/reinstate")
+        .admin()
+        .post(api::admin::reinstate_submission);
+    api.at("/admin/rounds")
+        .admin()
+        .get(api::admin::get_rounds_status)
+        .post(api::admin::force_round);
+    api.at("/admin/rounds/paused")
+        .admin()
+        .put(api::admin::set_rounds_paused);
+    api.at("/admin/house")
+        .admin()
+        .get(api::admin::get_house_strategies)
+        .post(api::admin::add_house_strategy);
+    api.at("/admin/house/:id")
+        .admin()
+        .delete(api::admin::delete_house_strategy);
+    api.at("/admin/users/:user_id/deadline")
+        .admin()
+        .put(api::admin::set_user_deadline);
 
     api.at("/events")
         .get(tide::sse::endpoint(api::events::process_events));
@@ -204,8 +318,15 @@ async fn main() -> tide::Result<()> {
 
     let state = app.state().clone();
 
+    for _ in 0..validation_queue::WORKERS {
+        let state = state.clone();
+        async_std::task::spawn(
+            async move { validation_queue::validation_worker(state).await.unwrap() },
+        );
+    }
+
     async_std::task::spawn(async move {
-        background_round_executor::background_round_executor(&state, scoreboard_signal)
+        background_round_executor::background_round_executor(&state, round_cache)
             .await
             .unwrap()
     });
@@ -0,0 +1,97 @@
+//! Reconstruction of the matches from the stored moves, following the rules in Fixture.java.
+
+use execution::matchmaker::PlayerResult;
+use serde::{Deserialize, Serialize};
+
+/// Value of the fields at the start of a match
+const INITIAL_FIELD: i32 = 1;
+
+/// The payoff function of the fixture
+fn f(x: i32) -> f64 {
+    let e = (x as f64).exp();
+    10.0 * e / (1.0 + e)
+}
+
+fn payoff(fields: &[i32; 3], own_move: i32, opponent_move: i32) -> f64 {
+    if own_move == opponent_move {
+        return 0.0;
+    }
+    // the moves of the completed turns are in bounds, unless the stored result is broken
+    match usize::try_from(own_move - 1)
+        .ok()
+        .and_then(|i| fields.get(i))
+    {
+        Some(&x) => f(x) - f(0),
+        None => 0.0,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Turn {
+    pub your_move: i32,
+    pub opponent_move: i32,
+    /// xA, xB and xC the players saw when making the moves
+    pub fields: [i32; 3],
+    pub your_payoff: f64,
+    pub opponent_payoff: f64,
+    /// Totals after the turn
+    pub your_score: f64,
+    pub opponent_score: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+    pub opponent_name: String,
+    pub turns: Vec<Turn>,
+    /// Fields after the last turn
+    pub final_fields: [i32; 3],
+    pub your_result: Result<f64, String>,
+    pub opponent_result: Result<f64, String>,
+}
+
+/// Replays the first `moves` turns of the match from the point of view of `you`.
+///
+/// The move that caused an error (if any) is not played, as in the fixture
+pub fn replay_match(moves: u32, you: &PlayerResult, opponent: &PlayerResult) -> Replay {
+    let mut fields = [INITIAL_FIELD; 3];
+    let mut turns = Vec::new();
+    let (mut your_score, mut opponent_score) = (0.0, 0.0);
+
+    for (&your_move, &opponent_move) in you
+        .moves
+        .iter()
+        .zip(opponent.moves.iter())
+        .take(moves as usize)
+    {
+        let your_payoff = payoff(&fields, your_move, opponent_move);
+        let opponent_payoff = payoff(&fields, opponent_move, your_move);
+        your_score += your_payoff;
+        opponent_score += opponent_payoff;
+
+        turns.push(Turn {
+            your_move,
+            opponent_move,
+            fields,
+            your_payoff,
+            opponent_payoff,
+            your_score,
+            opponent_score,
+        });
+
+        for (p, field) in fields.iter_mut().enumerate() {
+            let chosen = p as i32 == your_move - 1 || p as i32 == opponent_move - 1;
+            let dx = if chosen { -1 } else { 1 };
+            if *field + dx >= 0 {
+                *field += dx;
+            }
+        }
+    }
+
+    Replay {
+        opponent_name: opponent.player_name.clone(),
+        turns,
+        final_fields: fields,
+        your_result: you.outcome.clone(),
+        opponent_result: opponent.outcome.clone(),
+    }
+}
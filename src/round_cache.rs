@@ -0,0 +1,166 @@
+//! The scoreboard and the matches of every player, computed once after each round for all the
+//! requests and event streams to read.
+
+use crate::api::rounds::{
+    compute_final_standings, compute_player_matches, compute_scoreboard, scoreboard_ranks,
+    stored_scoreboard, PlayerMatches, Scoreboard,
+};
+use crate::contest::{ContestConfig, Phase};
+use crate::house::hidden_house_players;
+use crate::Database;
+use chrono::Utc;
+use entity::sea_orm::prelude::DateTimeUtc;
+use execution::matchmaker::PlayerResult;
+use std::collections::{BTreeSet, HashMap};
+use tracing::instrument;
+
+#[derive(Debug)]
+pub struct RoundCache {
+    /// The phase of the contest the cache was computed in
+    phase: Phase,
+    /// The live scoreboard
+    pub scoreboard: Scoreboard,
+    scoreboard_json: String,
+    /// The scoreboard shown to everyone but the admins, frozen during the freeze
+    public_scoreboard_json: String,
+    frozen_at: Option<DateTimeUtc>,
+    /// The hidden house strategies, left out of everything public
+    hidden: BTreeSet<String>,
+    /// Serialized `PlayerMatches` by user id
+    matches_json: HashMap<String, String>,
+    /// Serialized `PlayerMatches` of the users who did not play
+    no_matches_json: String,
+    /// Serialized official standings, once the final tournament is over
+    standings_json: Option<String>,
+}
+
+impl RoundCache {
+    #[instrument(skip_all)]
+    pub async fn compute(db: &Database, contest: &ContestConfig) -> anyhow::Result<Self> {
+        let scoring = &contest.scoring;
+        let hidden = hidden_house_players(db).await?;
+        let final_rounds = db.count_final_rounds().await?;
+        let standings_json = if final_rounds >= contest.final_rounds {
+            Some(serde_json::to_string(
+                &compute_final_standings(db, scoring).await?.without(&hidden),
+            )?)
+        } else {
+            None
+        };
+
+        let mut scoreboard = compute_scoreboard(db, scoring).await?;
+        let (rounds, _) = db.get_last_rounds_results(&scoring.window).await?;
+
+        let phase = contest
+            .schedule
+            .phase(Utc::now(), final_rounds, contest.final_rounds);
+        let freeze = contest.schedule.freeze;
+
+        let public_scoreboard = match (phase, freeze) {
+            (Phase::Frozen | Phase::Final, Some(freeze)) => Scoreboard {
+                frozen_at: Some(freeze),
+                ..stored_scoreboard(db.get_scoreboard_at(freeze).await?)
+            },
+            (Phase::Closed, Some(freeze)) => {
+                let frozen_ranks: HashMap<_, _> = db
+                    .get_scoreboard_at(freeze)
+                    .await?
+                    .into_iter()
+                    .map(|e| (e.user_id, e.rank))
+                    .collect();
+                scoreboard.rank_changes = Some(
+                    scoreboard_ranks(&scoreboard)
+                        .into_iter()
+                        .filter_map(|(user_id, rank, _)| {
+                            let frozen_rank = frozen_ranks.get(&user_id)?;
+                            Some((user_id, frozen_rank - rank))
+                        })
+                        .collect(),
+                );
+                scoreboard.clone()
+            }
+            _ => scoreboard.clone(),
+        }
+        .without(&hidden);
+
+        let mut by_player: HashMap<_, Vec<(u32, PlayerResult, PlayerResult)>> = HashMap::new();
+        for m in rounds.into_iter().flat_map(|(_, r)| r.0) {
+            // the matches against the hidden house strategies are left out of the public lists
+            if !hidden.contains(&m.player2.player_name) {
+                by_player
+                    .entry(m.player1.player_name.clone())
+                    .or_default()
+                    .push((m.moves, m.player1.clone(), m.player2.clone()));
+            }
+            if !hidden.contains(&m.player1.player_name) {
+                by_player
+                    .entry(m.player2.player_name.clone())
+                    .or_default()
+                    .push((m.moves, m.player2, m.player1));
+            }
+        }
+
+        let matches_json = by_player
+            .into_iter()
+            .map(|(player, matches)| {
+                // the scores of the opponents are as public as the scoreboard
+                let mut matches =
+                    compute_player_matches(matches, &public_scoreboard, &scoring.error_policy)?;
+                matches.round_time = scoreboard.datetime;
+                Ok((player, serde_json::to_string(&matches)?))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        let no_matches_json = serde_json::to_string(&PlayerMatches {
+            matches: Vec::new(),
+            round_time: scoreboard.datetime,
+            error_policy: scoring.error_policy.clone(),
+        })?;
+
+        Ok(RoundCache {
+            phase,
+            scoreboard_json: serde_json::to_string(&scoreboard)?,
+            public_scoreboard_json: serde_json::to_string(&public_scoreboard)?,
+            frozen_at: public_scoreboard.frozen_at,
+            hidden,
+            scoreboard,
+            matches_json,
+            no_matches_json,
+            standings_json,
+        })
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// The serialized scoreboard, the live one for the admins
+    pub fn scoreboard_json(&self, admin: bool) -> &str {
+        if admin {
+            &self.scoreboard_json
+        } else {
+            &self.public_scoreboard_json
+        }
+    }
+
+    /// The freeze time if the public scoreboard is frozen
+    pub fn frozen_at(&self) -> Option<DateTimeUtc> {
+        self.frozen_at
+    }
+
+    /// The players only the admins can see
+    pub fn hidden(&self) -> &BTreeSet<String> {
+        &self.hidden
+    }
+
+    pub fn standings_json(&self) -> Option<&str> {
+        self.standings_json.as_deref()
+    }
+
+    /// The serialized `PlayerMatches` of the user
+    pub fn matches_json(&self, user_id: &str) -> &str {
+        self.matches_json
+            .get(user_id)
+            .unwrap_or(&self.no_matches_json)
+    }
+}
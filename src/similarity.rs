@@ -0,0 +1,321 @@
+use entity::submission;
+use execution::lexer::{tokenize, TokenKind};
+use execution::matchmaker::DUMMY_STRATS;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// Number of normalized tokens in a hashed k-gram
+const K: usize = 12;
+/// Size of the winnowing window (in k-grams)
+const WINDOW: usize = 8;
+
+/// The code every strategy starts with; matches against it are not suspicious
+const TEMPLATE: &str =
+    include_str!("../template/src/gametheory/assignment2/your_strategy/Strat.java");
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SourceRef {
+    Submission { id: i32, user_id: String },
+    Dummy { name: String },
+}
+
+impl SourceRef {
+    fn owner(&self) -> &str {
+        match self {
+            SourceRef::Submission { user_id, .. } => user_id,
+            SourceRef::Dummy { name } => name,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SuspiciousPair {
+    pub first: SourceRef,
+    pub second: SourceRef,
+    /// Shared fingerprints over all fingerprints of both sources
+    pub similarity: f64,
+    /// Share of the fingerprints of the first source found in the second one
+    pub first_coverage: f64,
+    /// Share of the fingerprints of the second source found in the first one
+    pub second_coverage: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SimilarityReport {
+    pub pairs: Vec<SuspiciousPair>,
+}
+
+/// Replaces identifiers and literals with placeholders and drops the comments,
+/// so that renaming things or changing constants does not hide the copying
+fn normalized_tokens(code: &str) -> Vec<u64> {
+    tokenize(code)
+        .into_iter()
+        .filter_map(|t| {
+            let normalized = match t.kind {
+                TokenKind::Comment => return None,
+                TokenKind::Identifier => "<id>",
+                TokenKind::Number => "<num>",
+                TokenKind::String => "<str>",
+                TokenKind::Char => "<chr>",
+                TokenKind::Keyword | TokenKind::Symbol | TokenKind::Unknown => t.text,
+            };
+            let mut hasher = DefaultHasher::new();
+            normalized.hash(&mut hasher);
+            Some(hasher.finish())
+        })
+        .collect()
+}
+
+/// Selects the fingerprints of the code with the winnowing algorithm
+/// (see "Winnowing: Local Algorithms for Document Fingerprinting", Schleimer et al.)
+fn fingerprints(code: &str) -> HashSet<u64> {
+    let tokens = normalized_tokens(code);
+
+    let kgrams: Vec<u64> = tokens
+        .windows(K)
+        .map(|w| {
+            let mut hasher = DefaultHasher::new();
+            w.hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect();
+
+    if kgrams.len() < WINDOW {
+        return kgrams.iter().min().into_iter().copied().collect();
+    }
+
+    let mut res = HashSet::new();
+    let mut last_selected = None;
+    for (start, window) in kgrams.windows(WINDOW).enumerate() {
+        // the rightmost minimal hash in the window
+        let (offset, &hash) = window
+            .iter()
+            .enumerate()
+            .rev()
+            .min_by_key(|(_, &h)| h)
+            .unwrap();
+        let position = start + offset;
+        if last_selected != Some(position) {
+            res.insert(hash);
+            last_selected = Some(position);
+        }
+    }
+
+    res
+}
+
+struct Fingerprinted {
+    source: SourceRef,
+    fingerprints: HashSet<u64>,
+}
+
+fn compare(a: &Fingerprinted, b: &Fingerprinted) -> Option<SuspiciousPair> {
+    if a.fingerprints.is_empty() || b.fingerprints.is_empty() {
+        return None;
+    }
+
+    let shared = a.fingerprints.intersection(&b.fingerprints).count() as f64;
+    let total = a.fingerprints.union(&b.fingerprints).count() as f64;
+
+    Some(SuspiciousPair {
+        first: a.source.clone(),
+        second: b.source.clone(),
+        similarity: shared / total,
+        first_coverage: shared / a.fingerprints.len() as f64,
+        second_coverage: shared / b.fingerprints.len() as f64,
+    })
+}
+
+/// Compares the submissions of different users with each other and with the dummy strategies.
+///
+/// For each pair of users only their most similar submissions are reported.
+/// Pairs with similarity below `min_similarity` are omitted; the rest are sorted from the most similar.
+pub fn compute_similarity_report(
+    submissions: &[submission::Model],
+    min_similarity: f64,
+) -> SimilarityReport {
+    let template = fingerprints(TEMPLATE);
+
+    let sources: Vec<Fingerprinted> = DUMMY_STRATS
+        .iter()
+        .map(|(name, code)| {
+            (
+                SourceRef::Dummy {
+                    name: name.to_string(),
+                },
+                *code,
+            )
+        })
+        .chain(submissions.iter().map(|s| {
+            (
+                SourceRef::Submission {
+                    id: s.id,
+                    user_id: s.user_id.clone(),
+                },
+                s.code.as_str(),
+            )
+        }))
+        .map(|(source, code)| Fingerprinted {
+            source,
+            fingerprints: fingerprints(code).difference(&template).copied().collect(),
+        })
+        .collect();
+
+    let mut best_by_owners = BTreeMap::<(&str, &str), SuspiciousPair>::new();
+
+    for (i, a) in sources.iter().enumerate() {
+        for b in &sources[i + 1..] {
+            let owners = (a.source.owner(), b.source.owner());
+            let both_dummies = matches!(
+                (&a.source, &b.source),
+                (SourceRef::Dummy { .. }, SourceRef::Dummy { .. })
+            );
+            if owners.0 == owners.1 || both_dummies {
+                continue;
+            }
+            let owners = if owners.0 < owners.1 {
+                owners
+            } else {
+                (owners.1, owners.0)
+            };
+
+            let pair = match compare(a, b) {
+                Some(p) if p.similarity >= min_similarity => p,
+                _ => continue,
+            };
+
+            let best = best_by_owners.entry(owners).or_insert_with(|| pair.clone());
+            if pair.similarity > best.similarity {
+                *best = pair;
+            }
+        }
+    }
+
+    let mut pairs: Vec<_> = best_by_owners.into_values().collect();
+    pairs.sort_by(|a, b| {
+        b.similarity
+            .partial_cmp(&a.similarity)
+            .unwrap()
+            .then_with(|| a.first.cmp(&b.first))
+            .then_with(|| a.second.cmp(&b.second))
+    });
+
+    SimilarityReport { pairs }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    const STRATEGY: &str = r#"
+package gametheory.assignment2.your_strategy;
+
+import gametheory.assignment2.Player;
+
+public class Strat implements Player {
+  private int rounds = 0;
+  private int[] history = new int[3];
+
+  public void reset() { rounds = 0; history = new int[3]; }
+
+  public int move(int opponentLastMove, int xA, int xB, int xC) {
+    rounds++;
+    if (opponentLastMove > 0) { history[opponentLastMove - 1]++; }
+    int best = 1;
+    int[] fields = {xA, xB, xC};
+    for (int i = 1; i < 3; i++) {
+      if (fields[i] - history[i] > fields[best - 1] - history[best - 1]) { best = i + 1; }
+    }
+    return rounds % 7 == 0 ? (best % 3) + 1 : best;
+  }
+
+  public String getEmail() { return "someone@innopolis.university"; }
+}
+"#;
+
+    fn submission(id: i32, user_id: &str, code: &str) -> submission::Model {
+        submission::Model {
+            id,
+            user_id: user_id.to_string(),
+            code: code.to_string(),
+            datetime: Utc::now(),
+            valid: true,
+            files: None,
+            jdk_version: 17,
+            report: None,
+            late_penalty: None,
+            ranked: true,
+            invalidated: false,
+        }
+    }
+
+    #[test]
+    fn renamed_identifiers_still_match() {
+        let renamed = STRATEGY
+            .replace("rounds", "turnCounter")
+            .replace("history", "seen")
+            .replace("best", "choice")
+            .replace("fields", "values")
+            .replace("% 7", "% 5");
+        let report = compute_similarity_report(
+            &[
+                submission(1, "alice", STRATEGY),
+                submission(2, "bob", &renamed),
+            ],
+            0.5,
+        );
+
+        let pair = report
+            .pairs
+            .iter()
+            .find(|p| matches!(p.first, SourceRef::Submission { .. }))
+            .unwrap();
+        assert_eq!(
+            (&pair.first, &pair.second),
+            (
+                &SourceRef::Submission {
+                    id: 1,
+                    user_id: "alice".to_string()
+                },
+                &SourceRef::Submission {
+                    id: 2,
+                    user_id: "bob".to_string()
+                }
+            )
+        );
+        assert_eq!(pair.similarity, 1.0);
+    }
+
+    #[test]
+    fn template_only_code_scores_zero() {
+        let email = TEMPLATE.replace("your_email", "bob");
+        let report = compute_similarity_report(
+            &[
+                submission(1, "alice", TEMPLATE),
+                submission(2, "bob", &email),
+            ],
+            0.0,
+        );
+        assert!(report.pairs.is_empty());
+    }
+
+    #[test]
+    fn same_user_is_not_compared() {
+        let report = compute_similarity_report(
+            &[
+                submission(1, "alice", STRATEGY),
+                submission(2, "alice", STRATEGY),
+            ],
+            0.0,
+        );
+        assert!(report
+            .pairs
+            .iter()
+            .all(|p| matches!(p.first, SourceRef::Dummy { .. })
+                || matches!(p.second, SourceRef::Dummy { .. })));
+    }
+}
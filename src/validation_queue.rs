@@ -0,0 +1,274 @@
+use crate::api::admin::{validate_and_add_house_strategy, PendingHouseStrategy};
+use crate::api::sparring::{play_sparring, PendingSparring, SparringReport};
+use crate::api::submissions::{validate_and_store, PendingSubmission, ValidationReport};
+use crate::State;
+use async_std::channel::{bounded, Receiver, Sender, TrySendError};
+use chrono::Utc;
+use entity::sea_orm::prelude::DateTimeUtc;
+use futures_signals::signal::{Mutable, Signal};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tide::StatusCode;
+use tracing::{error, info, instrument};
+
+/// Number of jobs (validations, sparring matches and house strategy checks) run at the same time
+pub const WORKERS: usize = 2;
+/// Number of jobs that can wait for a worker, the new ones are rejected when it is full
+const CAPACITY: usize = 64;
+/// How long the finished jobs can be polled for
+const RETENTION: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Compiling,
+    Running,
+    Done {
+        submission_id: i32,
+        report: ValidationReport,
+    },
+    /// The sparring matches are over, nothing is stored
+    Sparred {
+        report: SparringReport,
+    },
+    /// The house strategy is validated, it is added only if it is valid
+    Added {
+        house_strategy_id: Option<i32>,
+        report: ValidationReport,
+    },
+    /// Something went wrong on our side, the submission was not stored
+    Failed {
+        error: String,
+    },
+}
+
+impl JobStatus {
+    fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Done { .. }
+                | JobStatus::Sparred { .. }
+                | JobStatus::Added { .. }
+                | JobStatus::Failed { .. }
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    Validation,
+    Sparring,
+    #[serde(rename = "house_strategy")]
+    HouseStrategy,
+}
+
+/// The work waiting in the queue
+#[derive(Debug)]
+pub enum QueuedJob {
+    Validation(PendingSubmission),
+    Sparring(PendingSparring),
+    HouseStrategy(PendingHouseStrategy),
+}
+
+impl QueuedJob {
+    fn user_id(&self) -> &str {
+        match self {
+            QueuedJob::Validation(s) => &s.user_id,
+            QueuedJob::Sparring(s) => &s.user_id,
+            QueuedJob::HouseStrategy(s) => &s.user_id,
+        }
+    }
+
+    fn kind(&self) -> JobKind {
+        match self {
+            QueuedJob::Validation(_) => JobKind::Validation,
+            QueuedJob::Sparring(_) => JobKind::Sparring,
+            QueuedJob::HouseStrategy(_) => JobKind::HouseStrategy,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ValidationJob {
+    pub id: u64,
+    pub user_id: String,
+    pub kind: JobKind,
+    pub updated: DateTimeUtc,
+    #[serde(flatten)]
+    pub status: JobStatus,
+}
+
+/// Submissions waiting for validation, sparring waiting to be played and the statuses of the recent jobs.
+///
+/// All of them share the workers, the house strategies uploaded by the admins too, so that the users cannot run more containers at the same time than there are workers
+pub struct ValidationQueue {
+    next_id: AtomicU64,
+    jobs: Mutable<Arc<BTreeMap<u64, ValidationJob>>>,
+    sender: Sender<(u64, QueuedJob)>,
+    receiver: Receiver<(u64, QueuedJob)>,
+}
+
+impl Debug for ValidationQueue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ValidationQueue")
+            .field("queued", &self.receiver.len())
+            .finish()
+    }
+}
+
+impl ValidationQueue {
+    pub fn new() -> Self {
+        let (sender, receiver) = bounded(CAPACITY);
+
+        ValidationQueue {
+            next_id: AtomicU64::new(1),
+            jobs: Mutable::new(Default::default()),
+            sender,
+            receiver,
+        }
+    }
+
+    /// Queues the job, returning its id.
+    ///
+    /// A user can have only one job of each kind waiting or in progress at a time
+    pub fn enqueue(&self, job: QueuedJob) -> tide::Result<u64> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let now = Utc::now();
+        let kind = job.kind();
+
+        let mut jobs = self.jobs.lock_mut();
+        let jobs = Arc::make_mut(&mut *jobs);
+        jobs.retain(|_, j| {
+            !j.status.is_finished()
+                || (now - j.updated)
+                    .to_std()
+                    .map_or(true, |age| age < RETENTION)
+        });
+
+        if jobs
+            .values()
+            .any(|j| j.user_id == job.user_id() && j.kind == kind && !j.status.is_finished())
+        {
+            return Err(tide::http::Error::from_str(
+                StatusCode::TooManyRequests,
+                match kind {
+                    JobKind::Validation => "Your previous submission is still being validated",
+                    JobKind::Sparring => "Your previous sparring is still being played",
+                    JobKind::HouseStrategy => {
+                        "Your previous house strategy is still being validated"
+                    }
+                },
+            ));
+        }
+
+        let user_id = job.user_id().to_string();
+        if let Err(e) = self.sender.try_send((id, job)) {
+            return Err(match e {
+                TrySendError::Full(_) => tide::http::Error::from_str(
+                    StatusCode::TooManyRequests,
+                    "Too many jobs are waiting for a worker, try again later",
+                ),
+                TrySendError::Closed(_) => tide::http::Error::from_str(
+                    StatusCode::InternalServerError,
+                    "The validation is not running",
+                ),
+            });
+        }
+
+        jobs.insert(
+            id,
+            ValidationJob {
+                id,
+                user_id,
+                kind,
+                updated: now,
+                status: JobStatus::Queued,
+            },
+        );
+
+        Ok(id)
+    }
+
+    pub fn get(&self, id: u64) -> Option<ValidationJob> {
+        self.jobs.lock_ref().get(&id).cloned()
+    }
+
+    fn set_status(&self, id: u64, status: JobStatus) {
+        let mut jobs = self.jobs.lock_mut();
+        if let Some(job) = Arc::make_mut(&mut *jobs).get_mut(&id) {
+            job.status = status;
+            job.updated = Utc::now();
+        }
+    }
+
+    /// Signals the jobs on each status change.
+    ///
+    /// Intermediate statuses may be skipped if they change quickly, but the final one is always there
+    pub fn signal(&self) -> impl Signal<Item = Arc<BTreeMap<u64, ValidationJob>>> {
+        self.jobs.signal_cloned()
+    }
+}
+
+#[instrument(skip(state, job))]
+async fn process_job(state: &State, id: u64, job: QueuedJob) {
+    let queue = &state.validation;
+    let on_status = |status| queue.set_status(id, status);
+
+    let status = match job {
+        QueuedJob::Validation(submission) => {
+            match validate_and_store(state, submission, on_status).await {
+                Ok((submission_id, report)) => JobStatus::Done {
+                    submission_id,
+                    report,
+                },
+                Err(e) => {
+                    error!("Validation job {id} failed:\n{e:?}");
+                    JobStatus::Failed {
+                        error: "Internal error while validating the submission".to_string(),
+                    }
+                }
+            }
+        }
+        QueuedJob::Sparring(sparring) => match play_sparring(state, sparring, on_status).await {
+            Ok(report) => JobStatus::Sparred { report },
+            Err(e) => {
+                error!("Sparring job {id} failed:\n{e:?}");
+                JobStatus::Failed {
+                    error: "Internal error while playing the sparring matches".to_string(),
+                }
+            }
+        },
+        QueuedJob::HouseStrategy(strategy) => {
+            match validate_and_add_house_strategy(state, strategy, on_status).await {
+                Ok((house_strategy_id, report)) => JobStatus::Added {
+                    house_strategy_id,
+                    report,
+                },
+                Err(e) => {
+                    error!("House strategy job {id} failed:\n{e:?}");
+                    JobStatus::Failed {
+                        error: "Internal error while adding the house strategy".to_string(),
+                    }
+                }
+            }
+        }
+    };
+
+    queue.set_status(id, status);
+}
+
+pub async fn validation_worker(state: State) -> anyhow::Result<()> {
+    while let Ok((id, job)) = state.validation.receiver.recv().await {
+        info!("Running the {:?} job {id} of {}", job.kind(), job.user_id());
+
+        process_job(&state, id, job).await;
+    }
+
+    Ok(())
+}